@@ -0,0 +1,148 @@
+use crate::AccountProvider;
+use crate::error::{AccountError, TokenFormatError};
+use crate::models::{
+    AccountQuery, AccountRecord, Capability, OperationCost, ProviderId, ProviderLimits,
+    ProviderMetadata, ProviderOp,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// A provider that wraps another one and changes only part of its behaviour.
+/// Implementors get `AccountProvider` for free: every method is forwarded to
+/// `decorated`, except those backed by the `on_*` hooks below, which they
+/// override. `invoke` keeps the trait default so name-based calls go through
+/// the hooks too. New trait methods are forwarded here once, for every
+/// decorator.
+#[async_trait]
+pub trait ProviderDecorator: Send + Sync {
+    fn decorated(&self) -> &dyn AccountProvider;
+
+    /// Backs `AccountProvider::refresh`.
+    async fn on_refresh(&self) -> anyhow::Result<()> {
+        self.decorated().refresh().await
+    }
+}
+
+#[async_trait]
+impl<T: ProviderDecorator> AccountProvider for T {
+    fn provider_name(&self) -> String {
+        self.decorated().provider_name()
+    }
+
+    fn provider_id(&self) -> ProviderId {
+        self.decorated().provider_id()
+    }
+
+    fn api_version(&self) -> u32 {
+        self.decorated().api_version()
+    }
+
+    fn capabilities(&self) -> Vec<Capability> {
+        self.decorated().capabilities()
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.decorated().supports(capability)
+    }
+
+    fn metadata(&self) -> ProviderMetadata {
+        self.decorated().metadata()
+    }
+
+    fn metadata_localized(&self, locale: &str) -> ProviderMetadata {
+        self.decorated().metadata_localized(locale)
+    }
+
+    fn operation_cost(&self, op: ProviderOp) -> OperationCost {
+        self.decorated().operation_cost(op)
+    }
+
+    async fn refresh(&self) -> anyhow::Result<()> {
+        self.on_refresh().await
+    }
+
+    fn supports_refresh(&self) -> bool {
+        self.decorated().supports_refresh()
+    }
+
+    async fn refresh_token(&self, account: &AccountRecord) -> anyhow::Result<AccountRecord> {
+        self.decorated().refresh_token(account).await
+    }
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        self.decorated().refresh_interval()
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        self.decorated().health_check().await
+    }
+
+    async fn shutdown(&self) -> anyhow::Result<()> {
+        self.decorated().shutdown().await
+    }
+
+    async fn limits(&self) -> anyhow::Result<ProviderLimits> {
+        self.decorated().limits().await
+    }
+
+    async fn list_accounts(&self) -> anyhow::Result<Vec<AccountRecord>> {
+        self.decorated().list_accounts().await
+    }
+
+    async fn list_accounts_capped(&self, max: usize) -> anyhow::Result<(Vec<AccountRecord>, bool)> {
+        self.decorated().list_accounts_capped(max).await
+    }
+
+    async fn list_accounts_query(
+        &self,
+        query: &AccountQuery,
+    ) -> anyhow::Result<Vec<AccountRecord>> {
+        self.decorated().list_accounts_query(query).await
+    }
+
+    async fn get_account(&self, account_id: &str) -> anyhow::Result<Option<AccountRecord>> {
+        self.decorated().get_account(account_id).await
+    }
+
+    async fn existing_ids(&self, ids: &[&str]) -> anyhow::Result<HashSet<String>> {
+        self.decorated().existing_ids(ids).await
+    }
+
+    fn required_extra_keys(&self) -> &[&str] {
+        self.decorated().required_extra_keys()
+    }
+
+    fn validate_token_format(&self, token: &str) -> Result<(), TokenFormatError> {
+        self.decorated().validate_token_format(token)
+    }
+
+    fn validate_account(&self, account: &AccountRecord) -> anyhow::Result<()> {
+        self.decorated().validate_account(account)
+    }
+
+    async fn upsert_account(&self, account: AccountRecord) -> anyhow::Result<AccountRecord> {
+        self.decorated().upsert_account(account).await
+    }
+
+    async fn store_account(&self, account: AccountRecord) -> anyhow::Result<AccountRecord> {
+        self.decorated().store_account(account).await
+    }
+
+    async fn validate_credentials(&self, creds: Value) -> anyhow::Result<AccountRecord> {
+        self.decorated().validate_credentials(creds).await
+    }
+
+    async fn remove_account(&self, account_id: &str) -> anyhow::Result<Option<AccountRecord>> {
+        self.decorated().remove_account(account_id).await
+    }
+
+    async fn verify_ownership(&self, account_id: &str, proof: Value) -> anyhow::Result<bool> {
+        self.decorated().verify_ownership(account_id, proof).await
+    }
+
+    fn is_retryable(&self, err: &AccountError) -> bool {
+        self.decorated().is_retryable(err)
+    }
+}
@@ -0,0 +1,405 @@
+use crate::models::{
+    AccountRecord, Capability, OperationCost, PRIMARY_TOKEN_PURPOSE, ProviderLimits, ProviderOp,
+};
+use crate::storage::{local_storage_get_json, local_storage_remove, local_storage_set_json};
+use crate::{ProviderEntry, registered_providers, registry_entries};
+use futures_util::future::join_all;
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+const SELF_TEST_KEY_PREFIX: &str = "account_self_test_";
+
+/// Last dashboard, with the user scope it was gathered for and when.
+static DASHBOARD_CACHE: Mutex<Option<DashboardSnapshot>> = Mutex::new(None);
+
+struct DashboardSnapshot {
+    scope: Option<String>,
+    gathered_at: Instant,
+    entries: Vec<ProviderDashboardEntry>,
+}
+
+impl DashboardSnapshot {
+    fn serves(&self, scope: Option<&str>, ttl: Duration) -> bool {
+        self.scope.as_deref() == scope && self.gathered_at.elapsed() < ttl
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub passed: bool,
+    /// Failure reason, or extra information for passing checks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl SelfTestCheck {
+    fn from_result(name: impl Into<String>, result: anyhow::Result<()>) -> Self {
+        Self {
+            name: name.into(),
+            passed: result.is_ok(),
+            detail: result.err().map(|err| format!("{err:#}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SelfTestReport {
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &SelfTestCheck> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+}
+
+/// Actively exercises the account subsystem: the frontend bridge, a storage
+/// write/read/remove round trip on a throwaway key, and every registered
+/// provider's `health_check`. Checks run independently, so one failure does
+/// not hide the others. Storage checks are skipped when the bridge is down.
+pub async fn self_test(app_handle: &AppHandle) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+    let probe_key = format!("{SELF_TEST_KEY_PREFIX}{}", unique_suffix());
+
+    let bridge = local_storage_get_json::<Value>(app_handle, &probe_key)
+        .await
+        .map(|_| ());
+    let bridge_up = bridge.is_ok();
+    report
+        .checks
+        .push(SelfTestCheck::from_result("frontbridge", bridge));
+
+    if bridge_up {
+        let round_trip = storage_round_trip(app_handle, &probe_key).await;
+        // Best-effort cleanup even if the round trip failed halfway.
+        let cleanup = local_storage_remove(app_handle, &probe_key).await;
+        report.checks.push(SelfTestCheck::from_result(
+            "storage_round_trip",
+            round_trip.and(cleanup),
+        ));
+    }
+
+    for provider in registered_providers() {
+        let name = format!("provider:{}", provider.provider_name());
+        let result = provider.health_check().await;
+        report.checks.push(SelfTestCheck::from_result(name, result));
+    }
+    report
+}
+
+async fn storage_round_trip(app_handle: &AppHandle, key: &str) -> anyhow::Result<()> {
+    let written = json!({ "probe": key });
+    local_storage_set_json(app_handle, key, &written).await?;
+    let read: Option<Value> = local_storage_get_json(app_handle, key).await?;
+    match read {
+        Some(read) if read == written => Ok(()),
+        Some(read) => anyhow::bail!("read back {read} instead of {written}"),
+        None => anyhow::bail!("value was missing right after writing it"),
+    }
+}
+
+/// Version of the `SubsystemDescription` JSON layout. Bumped on any breaking
+/// change (removed or renamed fields, changed meaning); new optional fields
+/// may be added without a bump.
+pub const SUBSYSTEM_DESCRIPTION_SCHEMA_VERSION: u32 = 3;
+
+/// Stable, machine-readable description of the account subsystem for
+/// external tooling. Serialized as:
+///
+/// `{ "schema_version": 3, "providers": [ { "id", "pinned", "display_name",
+///   "description"?, "api_version", "capabilities": [..], "supports_refresh",
+///   "required_extra_keys": [..], "operation_costs": { "refresh": "free", .. },
+///   "metadata": { <ProviderMetadataEntry::KEY>: <value> }, "accounts": [ { "id", "name",
+///   "has_avatar", "token_purposes": [..], "sealed", "extra_keys": [..] } ],
+///   "accounts_error": "<message>"? } ] }`
+///
+/// Accounts are summaries only: no token values or `extra` values appear.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SubsystemDescription {
+    pub schema_version: u32,
+    pub providers: Vec<ProviderDescription>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProviderDescription {
+    pub id: String,
+    pub pinned: bool,
+    /// From `AccountProvider::metadata`.
+    pub display_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub api_version: u32,
+    pub capabilities: Vec<Capability>,
+    pub supports_refresh: bool,
+    pub required_extra_keys: Vec<String>,
+    pub operation_costs: BTreeMap<&'static str, OperationCost>,
+    /// Registry metadata attached with `set_provider_metadata`.
+    pub metadata: Map<String, Value>,
+    pub accounts: Vec<AccountSummary>,
+    /// Why `accounts` is empty when listing them failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accounts_error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccountSummary {
+    pub id: String,
+    pub name: String,
+    pub has_avatar: bool,
+    /// Purposes with a token present; `"access"` covers the primary token.
+    pub token_purposes: Vec<String>,
+    pub sealed: bool,
+    pub extra_keys: Vec<String>,
+}
+
+impl AccountSummary {
+    pub fn of(account: &AccountRecord) -> Self {
+        let mut token_purposes: Vec<String> = account.tokens.keys().cloned().collect();
+        if account.token.is_some() && !account.tokens.contains_key(PRIMARY_TOKEN_PURPOSE) {
+            token_purposes.push(PRIMARY_TOKEN_PURPOSE.to_string());
+        }
+        token_purposes.sort();
+        Self {
+            id: account.id.clone(),
+            name: account.name.clone(),
+            has_avatar: account.avatar.is_some(),
+            token_purposes,
+            sealed: account.sealed,
+            extra_keys: account.extra.keys().cloned().collect(),
+        }
+    }
+}
+
+const DESCRIBED_OPS: [(&str, ProviderOp); 5] = [
+    ("refresh", ProviderOp::Refresh),
+    ("list_accounts", ProviderOp::ListAccounts),
+    ("get_account", ProviderOp::GetAccount),
+    ("upsert_account", ProviderOp::UpsertAccount),
+    ("remove_account", ProviderOp::RemoveAccount),
+];
+
+/// Describes every registered provider and its accounts; see
+/// `SubsystemDescription` for the schema. A provider whose accounts cannot be
+/// listed is still described, with `accounts_error` set.
+pub async fn describe_subsystem() -> anyhow::Result<SubsystemDescription> {
+    let mut providers = Vec::new();
+    for entry in registry_entries() {
+        let provider = &entry.provider;
+        let (accounts, accounts_error) = match provider.list_accounts().await {
+            Ok(accounts) => (accounts.iter().map(AccountSummary::of).collect(), None),
+            Err(err) => (Vec::new(), Some(format!("{err:#}"))),
+        };
+        let info = provider.metadata();
+        providers.push(ProviderDescription {
+            id: provider.provider_id().to_string(),
+            pinned: entry.pinned,
+            display_name: info.display_name,
+            description: info.description,
+            api_version: provider.api_version(),
+            capabilities: provider.capabilities(),
+            supports_refresh: provider.supports_refresh(),
+            required_extra_keys: provider
+                .required_extra_keys()
+                .iter()
+                .map(|key| key.to_string())
+                .collect(),
+            operation_costs: DESCRIBED_OPS
+                .iter()
+                .map(|(name, op)| (*name, provider.operation_cost(*op)))
+                .collect(),
+            metadata: entry
+                .metadata
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+            accounts,
+            accounts_error,
+        });
+    }
+    Ok(SubsystemDescription {
+        schema_version: SUBSYSTEM_DESCRIPTION_SCHEMA_VERSION,
+        providers,
+    })
+}
+
+/// One provider as shown on the settings dashboard. Each probe fails
+/// independently; a failed probe leaves its value empty and sets the
+/// matching `*_error`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProviderDashboardEntry {
+    pub id: String,
+    pub pinned: bool,
+    pub api_version: u32,
+    pub capabilities: Vec<Capability>,
+    pub supports_refresh: bool,
+    pub operation_costs: BTreeMap<&'static str, OperationCost>,
+    pub metadata: Map<String, Value>,
+    pub account_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accounts_error: Option<String>,
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_error: Option<String>,
+    pub limits: Option<ProviderLimits>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limits_error: Option<String>,
+}
+
+/// Dashboard view of every registered provider. Account listing, health
+/// check and limits are queried concurrently, across and within providers,
+/// and the result is cached: calls within `ttl` of the last gathering return
+/// it without touching any provider. The cache belongs to the user scope it
+/// was gathered in and is dropped whenever a provider is registered or
+/// removed; use `refresh_dashboard` to drop it for any other reason.
+pub async fn provider_dashboard(ttl: Duration) -> Vec<ProviderDashboardEntry> {
+    let scope = crate::user_scope();
+    if let Some(entries) = cached_dashboard(scope.as_deref(), ttl) {
+        return entries;
+    }
+    let entries = join_all(registry_entries().iter().map(dashboard_entry)).await;
+    *DASHBOARD_CACHE.lock().unwrap() = Some(DashboardSnapshot {
+        scope,
+        gathered_at: Instant::now(),
+        entries: entries.clone(),
+    });
+    entries
+}
+
+/// Forgets the cached dashboard so the next `provider_dashboard` call
+/// queries the providers again.
+pub fn refresh_dashboard() {
+    DASHBOARD_CACHE.lock().unwrap().take();
+}
+
+fn cached_dashboard(scope: Option<&str>, ttl: Duration) -> Option<Vec<ProviderDashboardEntry>> {
+    let cache = DASHBOARD_CACHE.lock().unwrap();
+    cache
+        .as_ref()
+        .filter(|snapshot| snapshot.serves(scope, ttl))
+        .map(|snapshot| snapshot.entries.clone())
+}
+
+async fn dashboard_entry(entry: &ProviderEntry) -> ProviderDashboardEntry {
+    let provider = &entry.provider;
+    let (accounts, health, limits) = futures_util::join!(
+        provider.list_accounts(),
+        provider.health_check(),
+        provider.limits()
+    );
+    let error = |err: anyhow::Error| format!("{err:#}");
+    ProviderDashboardEntry {
+        id: provider.provider_id().to_string(),
+        pinned: entry.pinned,
+        api_version: provider.api_version(),
+        capabilities: provider.capabilities(),
+        supports_refresh: provider.supports_refresh(),
+        operation_costs: DESCRIBED_OPS
+            .iter()
+            .map(|(name, op)| (*name, provider.operation_cost(*op)))
+            .collect(),
+        metadata: entry
+            .metadata
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect(),
+        account_count: accounts.as_ref().ok().map(Vec::len),
+        accounts_error: accounts.err().map(error),
+        healthy: health.is_ok(),
+        health_error: health.err().map(error),
+        limits: limits.as_ref().ok().copied(),
+        limits_error: limits.err().map(error),
+    }
+}
+
+fn unique_suffix() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TokenEntry;
+    use crate::testing::TestProvider;
+    use std::sync::Arc;
+
+    #[test]
+    fn report_passes_only_when_every_check_passes() {
+        let mut report = SelfTestReport {
+            checks: vec![SelfTestCheck::from_result("frontbridge", Ok(()))],
+        };
+        assert!(report.passed());
+
+        report.checks.push(SelfTestCheck::from_result(
+            "provider:xiaomi",
+            Err(anyhow::anyhow!("timeout")),
+        ));
+        assert!(!report.passed());
+        let failures: Vec<_> = report.failures().map(|check| check.name.as_str()).collect();
+        assert_eq!(failures, vec!["provider:xiaomi"]);
+        assert_eq!(report.checks[1].detail.as_deref(), Some("timeout"));
+    }
+
+    #[test]
+    fn dashboard_snapshots_serve_only_their_scope_within_ttl() {
+        let minute = Duration::from_secs(60);
+        let snapshot = DashboardSnapshot {
+            scope: Some("alice".to_string()),
+            gathered_at: Instant::now(),
+            entries: Vec::new(),
+        };
+        assert!(snapshot.serves(Some("alice"), minute));
+        assert!(!snapshot.serves(Some("alice"), Duration::ZERO));
+        assert!(!snapshot.serves(Some("bob"), minute));
+        assert!(!snapshot.serves(None, minute));
+    }
+
+    fn cache_empty_dashboard() {
+        *DASHBOARD_CACHE.lock().unwrap() = Some(DashboardSnapshot {
+            scope: crate::user_scope(),
+            gathered_at: Instant::now(),
+            entries: Vec::new(),
+        });
+    }
+
+    #[tokio::test]
+    async fn registry_changes_drop_the_cached_dashboard() {
+        let minute = Duration::from_secs(60);
+        let name = "test_dashboard_provider";
+        cache_empty_dashboard();
+        crate::add_account_provider(Arc::new(TestProvider::new(name))).await;
+        assert!(cached_dashboard(crate::user_scope().as_deref(), minute).is_none());
+
+        cache_empty_dashboard();
+        crate::remove_account_provider(name).await;
+        assert!(cached_dashboard(crate::user_scope().as_deref(), minute).is_none());
+
+        cache_empty_dashboard();
+        refresh_dashboard();
+        assert!(cached_dashboard(crate::user_scope().as_deref(), minute).is_none());
+    }
+
+    #[test]
+    fn account_summaries_never_carry_secret_values() {
+        let mut account = AccountRecord::new("1", "Alice").with_token("secret".to_string());
+        account.set_token_for("refresh", TokenEntry::new("refresh-secret"));
+        account.set_extra_value("email", json!("alice@example.com"));
+
+        let summary = AccountSummary::of(&account);
+        assert_eq!(summary.token_purposes, vec!["access", "refresh"]);
+        assert_eq!(summary.extra_keys, vec!["email"]);
+        let serialized = serde_json::to_string(&summary).unwrap();
+        assert!(!serialized.contains("secret") && !serialized.contains("example.com"));
+    }
+}
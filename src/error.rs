@@ -0,0 +1,204 @@
+use std::fmt;
+
+/// Typed failures surfaced by this crate. They travel inside `anyhow::Error`
+/// like every other error here; callers that need to react to a specific case
+/// use `err.downcast_ref::<AccountError>()`.
+#[derive(Debug, Clone)]
+pub enum AccountError {
+    MissingRequiredExtra {
+        account_id: String,
+        keys: Vec<String>,
+    },
+    RecordSealed {
+        account_id: String,
+    },
+    InvalidTokenFormat {
+        account_id: String,
+        reason: String,
+    },
+    UnknownMethod {
+        method: String,
+    },
+    InvalidArguments {
+        method: String,
+        reason: String,
+    },
+    MaintenanceMode {
+        store: String,
+    },
+    InvalidRecord {
+        account_id: String,
+        reason: String,
+    },
+    /// Methods report a missing id as `Ok(None)`; callers that treat it as a
+    /// failure convert with `.ok_or_else(|| AccountError::not_found(id))`.
+    NotFound {
+        account_id: String,
+    },
+    /// The frontend bridge circuit breaker is open after repeated failures.
+    BridgeUnavailable {
+        retry_after: std::time::Duration,
+    },
+    /// The frontend ran out of storage space for `key`.
+    StorageQuotaExceeded {
+        key: String,
+    },
+    /// The frontend could not serialize or parse the value of `key`.
+    StorageSerialization {
+        key: String,
+        message: String,
+    },
+    /// The frontend is not ready to serve storage requests yet.
+    FrontendNotReady,
+    /// Every id minted by `AccountStore::create_account_unique` was taken;
+    /// `account_id` is the last one tried.
+    IdCollision {
+        account_id: String,
+        attempts: usize,
+    },
+    /// A frontend failure code this crate does not know.
+    Frontend {
+        code: String,
+        message: String,
+    },
+    /// A provider operation gave up waiting on its backend.
+    Timeout,
+    /// A provider backend answered with a non-success HTTP status.
+    Http {
+        status: u16,
+    },
+}
+
+impl AccountError {
+    pub fn not_found(account_id: impl Into<String>) -> Self {
+        Self::NotFound {
+            account_id: account_id.into(),
+        }
+    }
+
+    /// Typed view of an error from anywhere in a provider: an `AccountError`
+    /// in the chain, or a timeout / HTTP status from reqwest or a tokio
+    /// timeout. `None` when nothing in the chain is recognized.
+    pub fn classify(err: &anyhow::Error) -> Option<Self> {
+        err.chain().find_map(|cause| {
+            if let Some(err) = cause.downcast_ref::<AccountError>() {
+                return Some(err.clone());
+            }
+            if cause.is::<tokio::time::error::Elapsed>() {
+                return Some(Self::Timeout);
+            }
+            let err = cause.downcast_ref::<reqwest::Error>()?;
+            if err.is_timeout() {
+                return Some(Self::Timeout);
+            }
+            err.status().map(|status| Self::Http {
+                status: status.as_u16(),
+            })
+        })
+    }
+
+    /// Whether retrying the same operation unchanged could succeed. This is
+    /// the default answer of `AccountProvider::is_retryable`; every variant is
+    /// listed so new ones have to pick a side.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::MissingRequiredExtra { .. }
+            | Self::RecordSealed { .. }
+            | Self::InvalidTokenFormat { .. }
+            | Self::UnknownMethod { .. }
+            | Self::InvalidArguments { .. }
+            | Self::InvalidRecord { .. }
+            | Self::NotFound { .. }
+            | Self::StorageQuotaExceeded { .. }
+            | Self::StorageSerialization { .. }
+            | Self::IdCollision { .. }
+            | Self::Frontend { .. } => false,
+            // Writers are meant to back off until the window ends, not retry
+            // straight into it.
+            Self::MaintenanceMode { .. } => false,
+            Self::BridgeUnavailable { .. } | Self::FrontendNotReady | Self::Timeout => true,
+            // Request timeout, too early, rate limited, server errors.
+            Self::Http { status } => matches!(status, 408 | 425 | 429 | 500..=599),
+        }
+    }
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingRequiredExtra { account_id, keys } => write!(
+                f,
+                "account {account_id} is missing required extra keys: {}",
+                keys.join(", ")
+            ),
+            Self::RecordSealed { account_id } => {
+                write!(f, "account {account_id} is sealed and cannot be modified")
+            }
+            Self::InvalidTokenFormat { account_id, reason } => {
+                write!(f, "account {account_id} has a malformed token: {reason}")
+            }
+            Self::UnknownMethod { method } => write!(
+                f,
+                "unknown provider method {method:?}; expected one of: {}",
+                crate::INVOKABLE_METHODS.join(", ")
+            ),
+            Self::InvalidArguments { method, reason } => {
+                write!(f, "invalid arguments for {method}: {reason}")
+            }
+            Self::MaintenanceMode { store } => {
+                write!(f, "account store {store} is read-only during maintenance")
+            }
+            Self::InvalidRecord { account_id, reason } => {
+                write!(f, "account {account_id:?} is invalid: {reason}")
+            }
+            Self::NotFound { account_id } => write!(f, "account {account_id} not found"),
+            Self::BridgeUnavailable { retry_after } => write!(
+                f,
+                "frontend bridge unavailable after repeated failures; retry in {}ms",
+                retry_after.as_millis()
+            ),
+            Self::StorageQuotaExceeded { key } => {
+                write!(f, "storage quota exceeded while writing {key}")
+            }
+            Self::StorageSerialization { key, message } => {
+                write!(f, "frontend could not serialize {key}: {message}")
+            }
+            Self::FrontendNotReady => write!(f, "frontend is not ready yet"),
+            Self::IdCollision {
+                account_id,
+                attempts,
+            } => write!(
+                f,
+                "no free account id after {attempts} attempts (last tried {account_id})"
+            ),
+            Self::Frontend { code, message } => write!(f, "frontend error {code}: {message}"),
+            Self::Timeout => write!(f, "provider operation timed out"),
+            Self::Http { status } => write!(f, "provider backend answered HTTP {status}"),
+        }
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+/// Why a provider rejected a token's shape. The reason is shown to users, so
+/// it should describe the expected format and never echo the token itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenFormatError {
+    pub reason: String,
+}
+
+impl TokenFormatError {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for TokenFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.reason)
+    }
+}
+
+impl std::error::Error for TokenFormatError {}
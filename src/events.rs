@@ -0,0 +1,311 @@
+use crate::models::{AccountRecord, ChangeSet};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::broadcast;
+
+const ACCOUNT_EVENT_CAPACITY: usize = 64;
+/// Number of recent events kept for `events_since`.
+pub const ACCOUNT_EVENT_LOG_CAPACITY: usize = 256;
+
+static ACCOUNT_EVENTS: OnceLock<broadcast::Sender<AccountEvent>> = OnceLock::new();
+static ACCOUNT_EVENT_LOG: OnceLock<Mutex<AccountEventLog>> = OnceLock::new();
+
+/// Change notification emitted by `AccountStore`. `seq` increases by one for
+/// every event in the process; `store` is the storage key that changed.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AccountEvent {
+    pub seq: u64,
+    pub store: String,
+    #[serde(flatten)]
+    pub change: AccountChange,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccountChange {
+    /// `changes` is the diff against the previous version, `None` when the
+    /// record is new, with token values redacted like the audit trail's. It is
+    /// only serialized by `EventPayloadMode::Diff`.
+    Upserted {
+        account: AccountRecord,
+        #[serde(skip)]
+        changes: Option<ChangeSet>,
+    },
+    Removed {
+        account_id: String,
+    },
+    Cleared,
+}
+
+impl AccountChange {
+    pub fn upserted(previous: Option<&AccountRecord>, account: AccountRecord) -> Self {
+        Self::Upserted {
+            changes: previous.map(|previous| previous.diff(&account).redacted()),
+            account,
+        }
+    }
+}
+
+/// How `AccountEvent::to_payload` encodes upserts for IPC.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EventPayloadMode {
+    /// Every upsert carries the whole record.
+    #[default]
+    Full,
+    /// Upserts of existing records become `{"type": "patched", "changes":
+    /// ChangeSet}` so the client patches its copy; new records stay full.
+    /// Token values in a patch are `REDACTED`, so a changed token only says
+    /// that it changed.
+    Diff,
+}
+
+impl AccountEvent {
+    pub fn to_payload(&self, mode: EventPayloadMode) -> Value {
+        match (&self.change, mode) {
+            (
+                AccountChange::Upserted {
+                    changes: Some(changes),
+                    ..
+                },
+                EventPayloadMode::Diff,
+            ) => json!({
+                "seq": self.seq,
+                "store": self.store,
+                "type": "patched",
+                "changes": changes,
+            }),
+            _ => serde_json::to_value(self).expect("account events serialize to JSON"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct AccountEventLog {
+    last_seq: u64,
+    recent: VecDeque<AccountEvent>,
+}
+
+fn account_event_sender() -> &'static broadcast::Sender<AccountEvent> {
+    ACCOUNT_EVENTS.get_or_init(|| broadcast::channel(ACCOUNT_EVENT_CAPACITY).0)
+}
+
+fn account_event_log() -> &'static Mutex<AccountEventLog> {
+    ACCOUNT_EVENT_LOG.get_or_init(|| Mutex::new(AccountEventLog::default()))
+}
+
+pub fn subscribe_account_events() -> broadcast::Receiver<AccountEvent> {
+    account_event_sender().subscribe()
+}
+
+/// Number of live receivers of the account event channel.
+pub fn account_event_subscriber_count() -> usize {
+    account_event_sender().receiver_count()
+}
+
+/// What `AccountEventSubscription::recv` yields.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountEventSignal {
+    Event(Box<AccountEvent>),
+    /// The receiver fell more than the channel capacity behind and `missed`
+    /// events were dropped for it. Resync with `events_since(last_seen_seq)`,
+    /// or reload fully if that replay does not start at `last_seen_seq + 1`.
+    Lagged {
+        missed: u64,
+        last_seen_seq: u64,
+    },
+}
+
+/// Receiver that reports lag as a signal instead of silently skipping, and
+/// remembers the last sequence number it delivered.
+pub struct AccountEventSubscription {
+    receiver: broadcast::Receiver<AccountEvent>,
+    last_seen_seq: u64,
+}
+
+impl AccountEventSubscription {
+    /// Next signal, or `None` once the channel is closed.
+    pub async fn recv(&mut self) -> Option<AccountEventSignal> {
+        match self.receiver.recv().await {
+            Ok(event) => {
+                self.last_seen_seq = event.seq;
+                Some(AccountEventSignal::Event(Box::new(event)))
+            }
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                log::warn!("[AccountEvents] subscriber lagged, {missed} events dropped");
+                Some(AccountEventSignal::Lagged {
+                    missed,
+                    last_seen_seq: self.last_seen_seq,
+                })
+            }
+            Err(broadcast::error::RecvError::Closed) => None,
+        }
+    }
+
+    pub fn last_seen_seq(&self) -> u64 {
+        self.last_seen_seq
+    }
+}
+
+pub fn subscribe_account_event_signals() -> AccountEventSubscription {
+    // Holding the log lock orders the subscription against `emit_account_event`,
+    // so `last_seen_seq` is exactly the last event this receiver will not see.
+    let log = account_event_log().lock().unwrap();
+    AccountEventSubscription {
+        receiver: account_event_sender().subscribe(),
+        last_seen_seq: log.last_seq,
+    }
+}
+
+/// Sequence number of the most recent event, 0 if nothing was emitted yet.
+pub fn latest_account_event_seq() -> u64 {
+    account_event_log().lock().unwrap().last_seq
+}
+
+/// Replays buffered events with a sequence number greater than `seq`, oldest
+/// first. Only the last `ACCOUNT_EVENT_LOG_CAPACITY` events are kept: when the
+/// first returned event is not `seq + 1` the client fell too far behind and
+/// must reload its state instead of patching it.
+pub fn events_since(seq: u64) -> Vec<AccountEvent> {
+    let log = account_event_log().lock().unwrap();
+    log.recent
+        .iter()
+        .filter(|event| event.seq > seq)
+        .cloned()
+        .collect()
+}
+
+pub(crate) fn emit_account_event(store: &str, change: AccountChange) {
+    let mut log = account_event_log().lock().unwrap();
+    log.last_seq += 1;
+    let event = AccountEvent {
+        seq: log.last_seq,
+        store: store.to_string(),
+        change,
+    };
+    if log.recent.len() == ACCOUNT_EVENT_LOG_CAPACITY {
+        log.recent.pop_front();
+    }
+    log.recent.push_back(event.clone());
+    // Sending under the log lock keeps broadcast order equal to `seq` order.
+    // It only fails when nobody is subscribed, which is fine.
+    let _ = account_event_sender().send(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_since_replays_in_sequence_order() {
+        let store = "test_events_since";
+        let before = latest_account_event_seq();
+        emit_account_event(
+            store,
+            AccountChange::upserted(None, AccountRecord::new("1", "Alice")),
+        );
+        emit_account_event(
+            store,
+            AccountChange::Removed {
+                account_id: "1".to_string(),
+            },
+        );
+
+        let replayed: Vec<_> = events_since(before)
+            .into_iter()
+            .filter(|event| event.store == store)
+            .collect();
+        assert_eq!(replayed.len(), 2);
+        assert!(replayed[0].seq > before && replayed[1].seq > replayed[0].seq);
+        assert_eq!(
+            replayed[1].change,
+            AccountChange::Removed {
+                account_id: "1".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn lagged_subscription_signals_where_to_resync_from() {
+        let store = "test_lagged_subscription";
+        let mut subscription = subscribe_account_event_signals();
+        let start = subscription.last_seen_seq();
+        assert!(account_event_subscriber_count() >= 1);
+
+        for _ in 0..ACCOUNT_EVENT_CAPACITY + 8 {
+            emit_account_event(store, AccountChange::Cleared);
+        }
+
+        match subscription.recv().await {
+            Some(AccountEventSignal::Lagged {
+                missed,
+                last_seen_seq,
+            }) => {
+                assert!(missed > 0);
+                assert_eq!(last_seen_seq, start);
+            }
+            other => panic!("expected a lag signal, got {other:?}"),
+        }
+        assert!(matches!(
+            subscription.recv().await,
+            Some(AccountEventSignal::Event(_))
+        ));
+        assert_eq!(
+            events_since(start).first().map(|event| event.seq),
+            Some(start + 1)
+        );
+    }
+
+    #[test]
+    fn diff_payloads_never_carry_token_values() {
+        let old = AccountRecord::new("1", "Alice").with_token("old-secret".to_string());
+        let new = AccountRecord::new("1", "Alice").with_token("new-secret".to_string());
+        let event = AccountEvent {
+            seq: 1,
+            store: "s".to_string(),
+            change: AccountChange::upserted(Some(&old), new),
+        };
+
+        let payload = event.to_payload(EventPayloadMode::Diff);
+        assert_eq!(payload["type"], "patched");
+        assert_eq!(payload["changes"]["changes"][0]["field"], "token");
+        let serialized = payload.to_string();
+        assert!(!serialized.contains("old-secret") && !serialized.contains("new-secret"));
+    }
+
+    #[test]
+    fn diff_payloads_patch_existing_records_only() {
+        let old = AccountRecord::new("1", "Alice");
+        let new = AccountRecord::new("1", "Alice W");
+        let event = |change| AccountEvent {
+            seq: 7,
+            store: "s".to_string(),
+            change,
+        };
+
+        let patched = event(AccountChange::upserted(Some(&old), new.clone()));
+        assert_eq!(
+            patched.to_payload(EventPayloadMode::Diff),
+            json!({
+                "seq": 7,
+                "store": "s",
+                "type": "patched",
+                "changes": {
+                    "account_id": "1",
+                    "changes": [{ "field": "name", "old": "Alice", "new": "Alice W" }]
+                }
+            })
+        );
+        assert_eq!(
+            patched.to_payload(EventPayloadMode::Full)["account"]["name"],
+            "Alice W"
+        );
+
+        let created = event(AccountChange::upserted(None, new));
+        assert_eq!(
+            created.to_payload(EventPayloadMode::Diff)["type"],
+            "upserted"
+        );
+    }
+}
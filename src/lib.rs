@@ -1,64 +1,1104 @@
+pub mod decorator;
+pub mod diagnostics;
+pub mod error;
+pub mod events;
+pub mod link;
+pub mod login;
 pub mod models;
 pub mod net;
+pub mod refresh;
+pub mod retry;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 pub mod storage;
+#[cfg(test)]
+mod testing;
 pub mod vivo;
 pub mod xiaomi;
 
-use crate::models::AccountRecord;
+use crate::models::{
+    AccountQuery, AccountRecord, Capability, OperationCost, PRIMARY_TOKEN_PURPOSE, ProviderId,
+    ProviderLimits, ProviderMetadata, ProviderMetadataEntry, ProviderOp, UnifiedAccount,
+    email_grouping_key,
+};
+use anyhow::Context;
 use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 
+pub use decorator::ProviderDecorator;
+pub use diagnostics::{
+    ProviderDashboardEntry, SUBSYSTEM_DESCRIPTION_SCHEMA_VERSION, SelfTestCheck, SelfTestReport,
+    SubsystemDescription, describe_subsystem, provider_dashboard, refresh_dashboard, self_test,
+};
+pub use error::{AccountError, TokenFormatError};
+pub use events::{
+    AccountChange, AccountEvent, AccountEventSignal, AccountEventSubscription, EventPayloadMode,
+    account_event_subscriber_count, events_since, subscribe_account_event_signals,
+    subscribe_account_events,
+};
+pub use link::{ResolvedAccount, resolve_account_link};
+pub use login::{LoginFieldMapping, LoginResponseMapper, upsert_login_response};
+pub use refresh::{
+    DedupRefreshProvider, RefreshSchedulerConfig, RefreshSchedulerHandle, RotateReport,
+    rotate_all_tokens, spawn_refresh_scheduler,
+};
+pub use retry::retry_operation;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteBackend;
 pub use storage::{
-    AccountStore, local_storage_get_json, local_storage_remove, local_storage_set_json,
+    AccountStore, ActiveFallback, AuditEntry, AuditOperation, BatchMode, BatchResult,
+    BridgeBreakerState, CompactReport, CompatibilityStatus, EmptyPolicy, ExportFormat,
+    ExportSecrets, FrontendStorage, ImportPlan, ImportReport, LoadedStore, ReconcileReport,
+    RemovedAccount, SingleAccountStore, StorageBackend, StoreLayout, TokenCipher, Tombstone,
+    bridge_breaker_state, check_storage_compatibility, check_storage_compatibility_in,
+    from_stored_value, is_maintenance_mode, local_storage_get_json, local_storage_keys,
+    local_storage_remove, local_storage_set_json, prune_orphaned_stores, prune_orphaned_stores_in,
+    set_maintenance_mode,
 };
 
-pub static ACCOUNT_PROVIDERS: OnceLock<Mutex<Vec<Arc<dyn AccountProvider>>>> = OnceLock::new();
+/// Current version of the `AccountProvider` API. Adding an optional trait
+/// method bumps it and adds a `Capability` whose `since_api_version` is the
+/// new value; providers declare it once they implement the method. Older
+/// providers keep reporting the version they were written against.
+pub const ACCOUNT_PROVIDER_API_VERSION: u32 = 4;
+
+/// Method names accepted by `AccountProvider::invoke`.
+pub const INVOKABLE_METHODS: &[&str] = &[
+    "refresh",
+    "health_check",
+    "limits",
+    "list_accounts",
+    "get_account",
+    "existing_ids",
+    "upsert_account",
+    "remove_account",
+    "validate_credentials",
+];
+
+pub static ACCOUNT_PROVIDERS: OnceLock<Mutex<Vec<ProviderEntry>>> = OnceLock::new();
+
+/// A provider as held by the registry.
+#[derive(Clone)]
+pub struct ProviderEntry {
+    provider: Arc<dyn AccountProvider>,
+    pinned: bool,
+    /// Registry-level bookkeeping attached by callers, keyed by
+    /// `ProviderMetadataEntry::KEY`; dropped together with the entry.
+    metadata: HashMap<&'static str, Value>,
+    /// User the provider belongs to; `None` for providers shared by all users.
+    user_scope: Option<String>,
+}
+
+impl ProviderEntry {
+    pub fn provider(&self) -> &Arc<dyn AccountProvider> {
+        &self.provider
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    pub fn metadata<T: ProviderMetadataEntry>(&self) -> Option<T> {
+        self.metadata
+            .get(T::KEY)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+
+    pub fn user_scope(&self) -> Option<&str> {
+        self.user_scope.as_deref()
+    }
+
+    fn new(provider: Arc<dyn AccountProvider>, pinned: bool) -> Self {
+        Self {
+            provider,
+            pinned,
+            metadata: HashMap::new(),
+            user_scope: None,
+        }
+    }
+
+    /// Global entries are visible to everyone, scoped ones only while their
+    /// user is the current scope.
+    fn is_visible(&self, scope: Option<&str>) -> bool {
+        self.user_scope.is_none() || self.user_scope.as_deref() == scope
+    }
+
+    fn is_visible_named(&self, name: &ProviderId, scope: Option<&str>) -> bool {
+        *name == self.provider.provider_name() && self.is_visible(scope)
+    }
+}
+
+static USER_SCOPE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Switches the current user for multi-user setups. Registry lookups and
+/// every aggregate (`unified_accounts`, `list_all_accounts`, the dashboard,
+/// the refresh scheduler...) then see global providers plus those registered
+/// for this user. `None`, the default, is the single-user global scope.
+pub fn set_user_scope(user_id: Option<String>) {
+    *USER_SCOPE.lock().unwrap() = user_id;
+    refresh_dashboard();
+}
+
+pub fn user_scope() -> Option<String> {
+    USER_SCOPE.lock().unwrap().clone()
+}
 
 pub async fn add_account_provider(provider: Arc<dyn AccountProvider>) {
+    register_provider(ProviderEntry::new(provider, false));
+}
+
+/// Registers a provider visible only while `user_id` is the current user
+/// scope; pair it with stores from `AccountStore::for_user`.
+pub async fn add_account_provider_for_user(
+    user_id: impl Into<String>,
+    provider: Arc<dyn AccountProvider>,
+) {
+    register_provider(ProviderEntry {
+        user_scope: Some(user_id.into()),
+        ..ProviderEntry::new(provider, false)
+    });
+}
+
+/// Registers a provider the core account system depends on. Pinned providers
+/// are skipped by `remove_account_provider` and `clear_account_providers`;
+/// only the `_force` variants remove them.
+pub async fn add_account_provider_pinned(provider: Arc<dyn AccountProvider>) {
+    register_provider(ProviderEntry::new(provider, true));
+}
+
+/// Every registry change drops the cached dashboard, which would otherwise
+/// keep showing the old provider set until its ttl ran out.
+fn register_provider(entry: ProviderEntry) {
     let providers = ACCOUNT_PROVIDERS.get_or_init(|| Mutex::new(Vec::new()));
-    let mut locked = providers.lock().unwrap();
-    locked.push(provider);
+    providers.lock().unwrap().push(entry);
+    refresh_dashboard();
 }
 
-pub async fn remove_account_provider(name: &str) {
+/// Unregisters the provider and awaits its `shutdown` before returning. The
+/// same holds for the `_force` and `clear_` variants. Removal by name only
+/// matches providers visible in the current user scope; the `clear_`
+/// variants cover every scope.
+pub async fn remove_account_provider(name: impl Into<ProviderId>) {
+    let name = name.into();
+    let scope = user_scope();
+    let removed = take_providers(|entry| {
+        let matches = entry.is_visible_named(&name, scope.as_deref());
+        if matches && entry.pinned {
+            log::warn!("[AccountProvider] refusing to remove pinned provider {name}");
+        }
+        matches && !entry.pinned
+    });
+    shutdown_providers(removed).await;
+}
+
+pub async fn remove_account_provider_force(name: impl Into<ProviderId>) {
+    let name = name.into();
+    let scope = user_scope();
+    let removed = take_providers(|entry| entry.is_visible_named(&name, scope.as_deref()));
+    shutdown_providers(removed).await;
+}
+
+/// Removes every provider that is not pinned.
+pub async fn clear_account_providers() {
+    let removed = take_providers(|entry| !entry.pinned);
+    shutdown_providers(removed).await;
+}
+
+pub async fn clear_account_providers_force() {
+    let removed = take_providers(|_| true);
+    shutdown_providers(removed).await;
+}
+
+/// Unregisters the entries matching `remove` and hands them back, so their
+/// shutdown runs without the registry lock held.
+fn take_providers(mut remove: impl FnMut(&ProviderEntry) -> bool) -> Vec<ProviderEntry> {
     let providers = ACCOUNT_PROVIDERS.get_or_init(|| Mutex::new(Vec::new()));
     let mut locked = providers.lock().unwrap();
-    locked.retain(|p| p.provider_name() != name);
+    let (removed, kept): (Vec<_>, _) = std::mem::take(&mut *locked)
+        .into_iter()
+        .partition(|entry| remove(entry));
+    *locked = kept;
+    drop(locked);
+    if !removed.is_empty() {
+        refresh_dashboard();
+    }
+    removed
+}
+
+async fn shutdown_providers(removed: Vec<ProviderEntry>) {
+    for entry in removed {
+        if let Err(err) = entry.provider.shutdown().await {
+            log::warn!(
+                "[AccountProvider] {} shutdown failed: {err:#}",
+                entry.provider.provider_name()
+            );
+        }
+    }
 }
 
-pub async fn get_account_provider(name: &str) -> Option<Arc<dyn AccountProvider>> {
+pub async fn get_account_provider(name: impl Into<ProviderId>) -> Option<Arc<dyn AccountProvider>> {
+    let name = name.into();
+    let scope = user_scope();
     let providers = ACCOUNT_PROVIDERS.get_or_init(|| Mutex::new(Vec::new()));
     let locked = providers.lock().unwrap();
-    for provider in locked.iter() {
-        if provider.provider_name() == name {
-            return Some(Arc::clone(provider));
+    for entry in locked.iter() {
+        if entry.is_visible_named(&name, scope.as_deref()) {
+            return Some(Arc::clone(&entry.provider));
         }
     }
     None
 }
 
+/// Attaches `value` to the registered provider `name` under `T::KEY`,
+/// replacing any earlier value with that key. This is registry bookkeeping
+/// (a fetched quota, the last health-check time) kept apart from the
+/// provider's own state, and it is discarded when the provider is
+/// unregistered.
+pub async fn set_provider_metadata<T: ProviderMetadataEntry>(
+    name: impl Into<ProviderId>,
+    value: &T,
+) -> anyhow::Result<()> {
+    let name = name.into();
+    let value = serde_json::to_value(value)
+        .with_context(|| format!("serialize metadata for provider {name}"))?;
+    let scope = user_scope();
+    let providers = ACCOUNT_PROVIDERS.get_or_init(|| Mutex::new(Vec::new()));
+    let mut locked = providers.lock().unwrap();
+    let entry = locked
+        .iter_mut()
+        .find(|entry| entry.is_visible_named(&name, scope.as_deref()))
+        .ok_or_else(|| anyhow::anyhow!("provider {name} is not registered"))?;
+    entry.metadata.insert(T::KEY, value);
+    Ok(())
+}
+
+/// Value of type `T` previously attached with `set_provider_metadata`.
+pub async fn get_provider_metadata<T: ProviderMetadataEntry>(
+    name: impl Into<ProviderId>,
+) -> Option<T> {
+    let name = name.into();
+    let scope = user_scope();
+    let providers = ACCOUNT_PROVIDERS.get_or_init(|| Mutex::new(Vec::new()));
+    let locked = providers.lock().unwrap();
+    locked
+        .iter()
+        .find(|entry| entry.is_visible_named(&name, scope.as_deref()))
+        .and_then(ProviderEntry::metadata)
+}
+
 pub async fn list_account_providers() -> Vec<String> {
+    registered_providers()
+        .iter()
+        .map(|provider| provider.provider_name())
+        .collect()
+}
+
+fn registered_providers() -> Vec<Arc<dyn AccountProvider>> {
+    registry_entries()
+        .into_iter()
+        .map(|entry| entry.provider)
+        .collect()
+}
+
+/// Display metadata of every provider visible in the current user scope, in
+/// registration order, localized for `locale`.
+pub fn localized_provider_metadata(locale: &str) -> Vec<(ProviderId, ProviderMetadata)> {
+    registered_providers()
+        .into_iter()
+        .map(|provider| (provider.provider_id(), provider.metadata_localized(locale)))
+        .collect()
+}
+
+/// Snapshot of the registry as seen from the current user scope, so callers
+/// never hold its lock across awaits.
+fn registry_entries() -> Vec<ProviderEntry> {
+    let scope = user_scope();
     let providers = ACCOUNT_PROVIDERS.get_or_init(|| Mutex::new(Vec::new()));
     let locked = providers.lock().unwrap();
-    locked.iter().map(|p| p.provider_name()).collect()
+    locked
+        .iter()
+        .filter(|entry| entry.is_visible(scope.as_deref()))
+        .cloned()
+        .collect()
+}
+
+/// Groups accounts of every registered provider by email.
+pub async fn unified_accounts() -> anyhow::Result<Vec<UnifiedAccount>> {
+    unified_accounts_by(email_grouping_key).await
+}
+
+/// Groups accounts of every registered provider by `grouping_key`. Accounts
+/// without a key form their own group keyed `"<provider>/<id>"`. Groups keep
+/// the order in which their first member was listed.
+pub async fn unified_accounts_by(
+    grouping_key: impl Fn(&AccountRecord) -> Option<String>,
+) -> anyhow::Result<Vec<UnifiedAccount>> {
+    let mut unified: Vec<UnifiedAccount> = Vec::new();
+    for provider in registered_providers() {
+        let provider_name = provider.provider_name();
+        let accounts = provider
+            .list_accounts()
+            .await
+            .with_context(|| format!("list accounts of provider {provider_name}"))?;
+        for account in accounts {
+            let key =
+                grouping_key(&account).unwrap_or_else(|| format!("{provider_name}/{}", account.id));
+            let member = (provider_name.clone(), account);
+            match unified.iter_mut().find(|group| group.key == key) {
+                Some(group) => group.members.push(member),
+                None => unified.push(UnifiedAccount {
+                    key,
+                    members: vec![member],
+                }),
+            }
+        }
+    }
+    Ok(unified)
+}
+
+/// Every account of every registered provider, paired with the provider
+/// name, in registry order.
+pub async fn list_all_accounts() -> anyhow::Result<Vec<(String, AccountRecord)>> {
+    let mut all = Vec::new();
+    for provider in registered_providers() {
+        let provider_name = provider.provider_name();
+        let accounts = provider
+            .list_accounts()
+            .await
+            .with_context(|| format!("list accounts of provider {provider_name}"))?;
+        all.extend(
+            accounts
+                .into_iter()
+                .map(|account| (provider_name.clone(), account)),
+        );
+    }
+    Ok(all)
+}
+
+/// Opaque position in the `list_all_accounts_page` order. It names the last
+/// returned record (provider and account id) rather than an offset, so records
+/// added or removed elsewhere do not shift later pages. It stays valid while
+/// its provider is registered, even if that record itself is removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor {
+    provider: String,
+    account_id: String,
+}
+
+/// One page of `list_all_accounts`, ordered by provider registration order,
+/// then account id. Pass the returned cursor back for the next page; `None`
+/// means there are no more records. A cursor whose provider has since been
+/// unregistered is expired and rejected.
+pub async fn list_all_accounts_page(
+    cursor: Option<Cursor>,
+    limit: usize,
+) -> anyhow::Result<(Vec<(String, AccountRecord)>, Option<Cursor>)> {
+    if limit == 0 {
+        return Ok((Vec::new(), cursor));
+    }
+    let providers = registered_providers();
+    let start = match &cursor {
+        Some(cursor) => providers
+            .iter()
+            .position(|provider| provider.provider_name() == cursor.provider)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "cursor expired: provider {} is no longer registered",
+                    cursor.provider
+                )
+            })?,
+        None => 0,
+    };
+    // One record past `limit` tells whether another page exists.
+    let mut page = Vec::with_capacity(limit + 1);
+    for provider in &providers[start..] {
+        let provider_name = provider.provider_name();
+        let mut accounts = provider
+            .list_accounts()
+            .await
+            .with_context(|| format!("list accounts of provider {provider_name}"))?;
+        accounts.sort_by(|a, b| a.id.cmp(&b.id));
+        let after = cursor
+            .as_ref()
+            .filter(|cursor| cursor.provider == provider_name)
+            .map(|cursor| cursor.account_id.as_str());
+        page.extend(
+            accounts
+                .into_iter()
+                .filter(|account| after.is_none_or(|after| account.id.as_str() > after))
+                .take(limit + 1 - page.len())
+                .map(|account| (provider_name.clone(), account)),
+        );
+        if page.len() > limit {
+            break;
+        }
+    }
+    let next = (page.len() > limit).then(|| {
+        page.truncate(limit);
+        let (provider, account) = &page[limit - 1];
+        Cursor {
+            provider: provider.clone(),
+            account_id: account.id.clone(),
+        }
+    });
+    Ok((page, next))
 }
 
 #[async_trait]
 pub trait AccountProvider: Send + Sync {
     fn provider_name(&self) -> String;
 
+    fn provider_id(&self) -> ProviderId {
+        ProviderId::from(self.provider_name())
+    }
+
+    /// `ACCOUNT_PROVIDER_API_VERSION` this provider was written against.
+    fn api_version(&self) -> u32 {
+        1
+    }
+
+    /// Optional behaviour this provider implements. The default only derives
+    /// `TokenRefresh` from `supports_refresh`; override to declare more.
+    fn capabilities(&self) -> Vec<Capability> {
+        if self.supports_refresh() {
+            vec![Capability::TokenRefresh]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Whether `capability` is declared and known to the provider's
+    /// `api_version`.
+    fn supports(&self, capability: Capability) -> bool {
+        self.api_version() >= capability.since_api_version()
+            && self.capabilities().contains(&capability)
+    }
+
+    /// Display metadata for the default locale; the default shows the
+    /// provider name with no description.
+    fn metadata(&self) -> ProviderMetadata {
+        ProviderMetadata {
+            display_name: self.provider_name(),
+            description: None,
+        }
+    }
+
+    /// `metadata` for `locale` (a BCP 47 tag such as `zh-CN` or `en`).
+    /// Providers without a translation for it return `metadata()`, which is
+    /// also the default; those that translate declare
+    /// `Capability::LocalizedMetadata`.
+    fn metadata_localized(&self, _locale: &str) -> ProviderMetadata {
+        self.metadata()
+    }
+
+    /// Purely advisory; store-backed providers keep the `Free` default and
+    /// remote providers report `Network`/`Expensive` for operations that hit
+    /// their servers.
+    fn operation_cost(&self, _op: ProviderOp) -> OperationCost {
+        OperationCost::Free
+    }
+
     async fn refresh(&self) -> anyhow::Result<()> {
         Ok(())
     }
 
+    /// Whether `refresh_token` is implemented; opts the provider into
+    /// `spawn_refresh_scheduler`.
+    fn supports_refresh(&self) -> bool {
+        false
+    }
+
+    /// Exchanges the account's expiring tokens for fresh ones and returns the
+    /// updated record; the scheduler stores it with `upsert_account`.
+    async fn refresh_token(&self, account: &AccountRecord) -> anyhow::Result<AccountRecord> {
+        Err(anyhow::anyhow!(
+            "provider {} does not support token refresh (account {})",
+            self.provider_name(),
+            account.id
+        ))
+    }
+
+    /// How often the refresh scheduler should check this provider; `None`
+    /// uses the scheduler's configured interval.
+    fn refresh_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Cheap liveness probe used by `self_test`; remote providers override it
+    /// to ping their backend.
+    async fn health_check(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Releases resources (connections, background tasks, watchers) when the
+    /// provider is unregistered. Called once, after the provider has left the
+    /// registry and before the removing call returns; errors are logged.
+    async fn shutdown(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Account/device quotas; the default reports everything as unknown.
+    async fn limits(&self) -> anyhow::Result<ProviderLimits> {
+        Ok(ProviderLimits::default())
+    }
+
     async fn list_accounts(&self) -> anyhow::Result<Vec<AccountRecord>>;
 
+    /// At most `max` accounts, plus whether more were available and dropped.
+    /// A safety valve for UI that renders the list; callers that need every
+    /// record use `list_accounts`. The default still fetches the full list,
+    /// so providers with server-side limits should override it.
+    async fn list_accounts_capped(&self, max: usize) -> anyhow::Result<(Vec<AccountRecord>, bool)> {
+        let mut accounts = self.list_accounts().await?;
+        let truncated = accounts.len() > max;
+        accounts.truncate(max);
+        Ok((accounts, truncated))
+    }
+
+    /// Accounts matching `query`. The default filters `list_accounts` in
+    /// memory; remote providers can translate `query.conditions()` into a
+    /// server-side filter (and declare `Capability::ServerSideQuery`).
+    async fn list_accounts_query(
+        &self,
+        query: &AccountQuery,
+    ) -> anyhow::Result<Vec<AccountRecord>> {
+        Ok(query.apply(self.list_accounts().await?))
+    }
+
     async fn get_account(&self, account_id: &str) -> anyhow::Result<Option<AccountRecord>> {
         let accounts = self.list_accounts().await?;
         Ok(accounts.into_iter().find(|acc| acc.id == account_id))
     }
 
-    async fn upsert_account(&self, account: AccountRecord) -> anyhow::Result<AccountRecord>;
+    /// Which of `ids` this provider already has. The result is a set, so the
+    /// order of `ids` does not matter; remote providers can override this to
+    /// answer with a single query.
+    async fn existing_ids(&self, ids: &[&str]) -> anyhow::Result<HashSet<String>> {
+        let accounts = self.list_accounts().await?;
+        Ok(accounts
+            .into_iter()
+            .filter(|acc| ids.contains(&acc.id.as_str()))
+            .map(|acc| acc.id)
+            .collect())
+    }
+
+    /// `extra` keys every record of this provider must carry.
+    fn required_extra_keys(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Opt-in shape check for tokens (JWT, prefixed, ...) so a pasted wrong
+    /// value is rejected up front instead of failing later at auth time. The
+    /// default accepts anything.
+    fn validate_token_format(&self, _token: &str) -> Result<(), TokenFormatError> {
+        Ok(())
+    }
+
+    /// Boundary check for incoming records, run by `upsert_account` before
+    /// anything is stored.
+    fn validate_account(&self, account: &AccountRecord) -> anyhow::Result<()> {
+        let missing = account.missing_extra_keys(self.required_extra_keys());
+        if !missing.is_empty() {
+            return Err(AccountError::MissingRequiredExtra {
+                account_id: account.id.clone(),
+                keys: missing,
+            }
+            .into());
+        }
+        // Both places the primary token can live are checked, in case a
+        // caller updated only one of them.
+        let primary = account.token.iter().chain(
+            account
+                .tokens
+                .get(PRIMARY_TOKEN_PURPOSE)
+                .map(|entry| &entry.value),
+        );
+        for token in primary {
+            if let Err(err) = self.validate_token_format(token) {
+                return Err(AccountError::InvalidTokenFormat {
+                    account_id: account.id.clone(),
+                    reason: err.reason,
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Stores `account` after `validate_account` accepted it, so records
+    /// missing required `extra` keys or carrying a malformed token never
+    /// reach `store_account`. Override `store_account`, not this.
+    async fn upsert_account(&self, account: AccountRecord) -> anyhow::Result<AccountRecord> {
+        self.validate_account(&account)?;
+        self.store_account(account).await
+    }
+
+    /// Inserts or replaces `account` in the provider's storage. Only called
+    /// through `upsert_account`, which validates the record first.
+    async fn store_account(&self, account: AccountRecord) -> anyhow::Result<AccountRecord>;
+
+    /// Checks login credentials with the provider and returns the record to
+    /// upsert, without persisting anything, so a login form can show the
+    /// error in place. The default reads `creds` as an `AccountRecord` and
+    /// runs `validate_account` on it; remote providers override it (and
+    /// declare `Capability::CredentialValidation`) to ask their backend.
+    async fn validate_credentials(&self, creds: Value) -> anyhow::Result<AccountRecord> {
+        let account: AccountRecord =
+            serde_json::from_value(creds).map_err(|err| AccountError::InvalidArguments {
+                method: "validate_credentials".to_string(),
+                reason: err.to_string(),
+            })?;
+        self.validate_account(&account)?;
+        Ok(account)
+    }
+
+    /// Returns the removed record, or `None` when `account_id` is unknown.
+    async fn remove_account(&self, account_id: &str) -> anyhow::Result<Option<AccountRecord>>;
+
+    /// Confirms the current session owns `account_id` before a
+    /// security-sensitive action. `proof` is provider-defined (a password
+    /// re-entry, a signed challenge...). The default trusts local ownership
+    /// and returns `Ok(true)`; remote providers override it to verify with
+    /// their backend. `Ok(false)` means verification ran and failed.
+    async fn verify_ownership(&self, _account_id: &str, _proof: Value) -> anyhow::Result<bool> {
+        Ok(true)
+    }
+
+    /// Retry classification consulted by `retry_operation`, which maps
+    /// failures with `AccountError::classify` first (reqwest and tokio
+    /// timeouts become `Timeout`, status errors `Http`). The default is
+    /// `AccountError::is_transient`; providers override it to classify their
+    /// own conditions.
+    fn is_retryable(&self, err: &AccountError) -> bool {
+        err.is_transient()
+    }
+
+    /// Name-based entry point for plugin/scripting hosts. `args` is a JSON
+    /// object with camelCase keys (`{"accountId": ..}`, `{"ids": [..]}`,
+    /// `{"account": {..}}`; `null` or `{}` for methods without arguments) and
+    /// the result is the method's return value as JSON. Unknown methods and
+    /// malformed arguments fail with `AccountError::UnknownMethod` /
+    /// `AccountError::InvalidArguments`.
+    async fn invoke(&self, method: &str, args: Value) -> anyhow::Result<Value> {
+        let result = match method {
+            "refresh" => {
+                invoke_args::<NoArgs>(method, args)?;
+                self.refresh().await?;
+                Value::Null
+            }
+            "health_check" => {
+                invoke_args::<NoArgs>(method, args)?;
+                self.health_check().await?;
+                Value::Null
+            }
+            "limits" => {
+                invoke_args::<NoArgs>(method, args)?;
+                serde_json::to_value(self.limits().await?)?
+            }
+            "list_accounts" => {
+                invoke_args::<NoArgs>(method, args)?;
+                serde_json::to_value(self.list_accounts().await?)?
+            }
+            "get_account" => {
+                let args: AccountIdArgs = invoke_args(method, args)?;
+                serde_json::to_value(self.get_account(&args.account_id).await?)?
+            }
+            "existing_ids" => {
+                let args: IdsArgs = invoke_args(method, args)?;
+                let ids: Vec<&str> = args.ids.iter().map(String::as_str).collect();
+                let mut existing: Vec<String> =
+                    self.existing_ids(&ids).await?.into_iter().collect();
+                existing.sort();
+                serde_json::to_value(existing)?
+            }
+            "upsert_account" => {
+                let args: AccountArgs = invoke_args(method, args)?;
+                serde_json::to_value(self.upsert_account(args.account).await?)?
+            }
+            "remove_account" => {
+                let args: AccountIdArgs = invoke_args(method, args)?;
+                serde_json::to_value(self.remove_account(&args.account_id).await?)?
+            }
+            "validate_credentials" => {
+                let args: CredentialsArgs = invoke_args(method, args)?;
+                serde_json::to_value(self.validate_credentials(args.credentials).await?)?
+            }
+            _ => {
+                return Err(AccountError::UnknownMethod {
+                    method: method.to_string(),
+                }
+                .into());
+            }
+        };
+        Ok(result)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoArgs {}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct AccountIdArgs {
+    account_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IdsArgs {
+    ids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountArgs {
+    account: AccountRecord,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CredentialsArgs {
+    credentials: Value,
+}
+
+fn invoke_args<T: DeserializeOwned>(method: &str, args: Value) -> anyhow::Result<T> {
+    let args = if args.is_null() {
+        Value::Object(Default::default())
+    } else {
+        args
+    };
+    serde_json::from_value(args).map_err(|err| {
+        AccountError::InvalidArguments {
+            method: method.to_string(),
+            reason: err.to_string(),
+        }
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestProvider;
+    use std::sync::atomic::Ordering;
+
+    fn bearer() -> TestProvider {
+        TestProvider::new("bearer").token_prefix("Bearer ")
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct FetchedQuota(u32);
+
+    impl ProviderMetadataEntry for FetchedQuota {
+        const KEY: &'static str = "fetched_quota";
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct LastHealthCheck(u64);
+
+    impl ProviderMetadataEntry for LastHealthCheck {
+        const KEY: &'static str = "last_health_check";
+    }
+
+    #[test]
+    fn localized_metadata_falls_back_to_the_default_locale() {
+        let provider = TestProvider::new("bilingual")
+            .display_name("Mi Account")
+            .translated("zh-CN", "小米账号");
+
+        assert_eq!(
+            provider.metadata_localized("zh-CN").display_name,
+            "小米账号"
+        );
+        assert_eq!(provider.metadata_localized("fr").display_name, "Mi Account");
+        assert_eq!(bearer().metadata_localized("zh-CN").display_name, "bearer");
+    }
+
+    #[tokio::test]
+    async fn provider_metadata_lives_and_dies_with_the_entry() {
+        let name = "test_metadata_provider";
+        assert!(set_provider_metadata(name, &FetchedQuota(5)).await.is_err());
+
+        add_account_provider(Arc::new(TestProvider::new(name))).await;
+        set_provider_metadata(name, &FetchedQuota(5)).await.unwrap();
+        assert_eq!(
+            get_provider_metadata::<FetchedQuota>(name).await,
+            Some(FetchedQuota(5))
+        );
+        assert_eq!(get_provider_metadata::<LastHealthCheck>(name).await, None);
+        let described = describe_subsystem().await.unwrap();
+        let described = described
+            .providers
+            .iter()
+            .find(|provider| provider.id == name)
+            .unwrap();
+        assert_eq!(described.metadata["fetched_quota"], serde_json::json!(5));
+
+        remove_account_provider(name).await;
+        add_account_provider(Arc::new(TestProvider::new(name))).await;
+        assert_eq!(get_provider_metadata::<FetchedQuota>(name).await, None);
+        remove_account_provider(name).await;
+    }
+
+    #[tokio::test]
+    async fn described_providers_carry_their_metadata_and_capabilities() {
+        let name = "test_described_provider";
+        let provider = TestProvider::new(name)
+            .display_name("Described")
+            .refreshable(&[]);
+        add_account_provider(Arc::new(provider)).await;
+
+        let described = describe_subsystem().await.unwrap();
+        remove_account_provider(name).await;
+        assert_eq!(
+            described.schema_version,
+            SUBSYSTEM_DESCRIPTION_SCHEMA_VERSION
+        );
+        let described = serde_json::to_value(
+            described
+                .providers
+                .iter()
+                .find(|provider| provider.id == name)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(described["display_name"], "Described");
+        assert!(described.get("description").is_none());
+        assert_eq!(described["api_version"], ACCOUNT_PROVIDER_API_VERSION);
+        assert_eq!(
+            described["capabilities"],
+            serde_json::json!(["token_refresh"])
+        );
+        assert_eq!(described["supports_refresh"], true);
+    }
+
+    #[tokio::test]
+    async fn removal_awaits_shutdown_of_the_removed_provider_only() {
+        let removed = Arc::new(TestProvider::new("test_closing_a"));
+        let kept = Arc::new(TestProvider::new("test_closing_b"));
+        add_account_provider(removed.clone()).await;
+        add_account_provider_pinned(kept.clone()).await;
+
+        remove_account_provider("test_closing_a").await;
+        remove_account_provider("test_closing_b").await;
+        assert_eq!(removed.shutdowns.load(Ordering::SeqCst), 1);
+        assert_eq!(kept.shutdowns.load(Ordering::SeqCst), 0);
+
+        remove_account_provider_force("test_closing_b").await;
+        assert_eq!(kept.shutdowns.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn cursor_pages_stay_consistent_when_records_change() {
+        let provider =
+            Arc::new(TestProvider::new("test_paged_provider").with_ids(&["d", "b", "a", "c"]));
+        add_account_provider(provider.clone()).await;
+        let ids = |page: &[(String, AccountRecord)]| -> Vec<String> {
+            page.iter()
+                .filter(|(name, _)| name == "test_paged_provider")
+                .map(|(_, account)| account.id.clone())
+                .collect()
+        };
+
+        let (first, cursor) = list_all_accounts_page(None, 2).await.unwrap();
+        assert_eq!(ids(&first), vec!["a", "b"]);
+        provider.remove_account("a").await.unwrap();
+        provider
+            .upsert_account(AccountRecord::new("0", "0"))
+            .await
+            .unwrap();
+        let (second, cursor) = list_all_accounts_page(cursor, 2).await.unwrap();
+        assert_eq!(ids(&second), vec!["c", "d"]);
+        assert!(cursor.is_none());
+
+        remove_account_provider("test_paged_provider").await;
+        let stale = Cursor {
+            provider: "test_paged_provider".to_string(),
+            account_id: "b".to_string(),
+        };
+        assert!(list_all_accounts_page(Some(stale), 2).await.is_err());
+    }
+
+    #[test]
+    fn scoped_entries_are_visible_only_to_their_user() {
+        let global = ProviderEntry::new(Arc::new(bearer()), false);
+        let scoped = ProviderEntry {
+            user_scope: Some("alice".to_string()),
+            ..global.clone()
+        };
+        let name = ProviderId::from("bearer");
+        assert!(global.is_visible(None) && global.is_visible(Some("bob")));
+        assert!(scoped.is_visible_named(&name, Some("alice")));
+        assert!(!scoped.is_visible(Some("bob")));
+        assert!(!scoped.is_visible(None));
+    }
+
+    #[tokio::test]
+    async fn invoked_upserts_reject_records_missing_required_extra() {
+        let provider = TestProvider::new("strict").requiring(&["region"]);
+        let err = provider
+            .invoke(
+                "upsert_account",
+                serde_json::json!({ "account": { "id": "1", "name": "Alice" } }),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AccountError>(),
+            Some(AccountError::MissingRequiredExtra { keys, .. }) if keys == &["region"]
+        ));
+        assert!(provider.stored().is_empty());
 
-    async fn remove_account(&self, account_id: &str) -> anyhow::Result<()>;
+        provider
+            .invoke(
+                "upsert_account",
+                serde_json::json!({ "account": { "id": "1", "name": "Alice", "extra": { "region": "cn" } } }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(provider.stored().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn direct_upserts_are_validated_too() {
+        let provider = Arc::new(TestProvider::new("strict").requiring(&["region"]));
+        let err = provider
+            .upsert_account(AccountRecord::new("1", "Alice"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AccountError>(),
+            Some(AccountError::MissingRequiredExtra { .. })
+        ));
+        let wrapped = refresh::DedupRefreshProvider::new(provider.clone());
+        assert!(
+            wrapped
+                .upsert_account(AccountRecord::new("1", "Alice"))
+                .await
+                .is_err()
+        );
+        assert!(provider.stored().is_empty());
+    }
+
+    #[test]
+    fn validate_account_rejects_malformed_tokens_only_when_present() {
+        let provider = bearer();
+        let account = AccountRecord::new("1", "Alice");
+        assert!(provider.validate_account(&account).is_ok());
+        assert!(
+            provider
+                .validate_account(&account.clone().with_token("Bearer abc".to_string()))
+                .is_ok()
+        );
+
+        let err = provider
+            .validate_account(&account.with_token("abc".to_string()))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AccountError>(),
+            Some(AccountError::InvalidTokenFormat { account_id, .. }) if account_id == "1"
+        ));
+        assert!(!err.to_string().contains("abc"));
+    }
+
+    #[tokio::test]
+    async fn default_credential_validation_echoes_a_valid_record() {
+        let provider = bearer();
+        let account = provider
+            .validate_credentials(serde_json::json!({
+                "id": "1",
+                "name": "Alice",
+                "token": "Bearer abc",
+            }))
+            .await
+            .unwrap();
+        assert_eq!(account.token.as_deref(), Some("Bearer abc"));
+
+        let err = provider
+            .validate_credentials(serde_json::json!({ "id": "1", "name": "Alice", "token": "abc" }))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AccountError>(),
+            Some(AccountError::InvalidTokenFormat { .. })
+        ));
+        let err = provider
+            .validate_credentials(serde_json::json!({ "username": "alice" }))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AccountError>(),
+            Some(AccountError::InvalidArguments { .. })
+        ));
+    }
+
+    #[test]
+    fn capabilities_need_a_declaration_and_a_recent_enough_api_version() {
+        let legacy = |version| {
+            TestProvider::new("legacy")
+                .api_version(version)
+                .capabilities(vec![Capability::HealthCheck])
+        };
+
+        assert!(!bearer().supports(Capability::HealthCheck));
+        assert!(legacy(ACCOUNT_PROVIDER_API_VERSION).supports(Capability::HealthCheck));
+        assert!(!legacy(ACCOUNT_PROVIDER_API_VERSION).supports(Capability::Limits));
+        assert!(!legacy(0).supports(Capability::HealthCheck));
+    }
+
+    #[tokio::test]
+    async fn capped_listing_truncates_and_flags_overflow() {
+        let crowded = TestProvider::new("crowded").with_ids(&["0", "1", "2", "3", "4"]);
+
+        let (accounts, truncated) = crowded.list_accounts_capped(3).await.unwrap();
+        assert_eq!(accounts.len(), 3);
+        assert!(truncated);
+        let (accounts, truncated) = crowded.list_accounts_capped(5).await.unwrap();
+        assert_eq!(accounts.len(), 5);
+        assert!(!truncated);
+    }
+
+    #[tokio::test]
+    async fn invoke_dispatches_by_name_and_validates_arguments() {
+        let provider = bearer();
+        let account = provider
+            .invoke(
+                "upsert_account",
+                serde_json::json!({ "account": { "id": "1", "name": "Alice" } }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(account["id"], "1");
+        assert_eq!(
+            provider.invoke("list_accounts", Value::Null).await.unwrap(),
+            serde_json::json!([{ "id": "1", "name": "Alice", "extra": {} }])
+        );
+        assert_eq!(
+            provider
+                .invoke("remove_account", serde_json::json!({ "accountId": "9" }))
+                .await
+                .unwrap(),
+            Value::Null
+        );
+
+        let unknown = provider.invoke("drop_all", Value::Null).await.unwrap_err();
+        assert!(matches!(
+            unknown.downcast_ref::<AccountError>(),
+            Some(AccountError::UnknownMethod { .. })
+        ));
+        let malformed = provider
+            .invoke("get_account", serde_json::json!({ "id": "1" }))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            malformed.downcast_ref::<AccountError>(),
+            Some(AccountError::InvalidArguments { method, .. }) if method == "get_account"
+        ));
+    }
 }
@@ -0,0 +1,109 @@
+use crate::get_account_provider;
+use crate::models::AccountRecord;
+use anyhow::{Context, Result, anyhow};
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use serde::Serialize;
+use url::Url;
+
+const ACCOUNT_LINK_SCHEME: &str = "astrobox";
+const ACCOUNT_LINK_HOST: &str = "account";
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ResolvedAccount {
+    Found {
+        provider: String,
+        account: AccountRecord,
+    },
+    UnknownProvider {
+        provider: String,
+    },
+    AccountNotFound {
+        provider: String,
+        account_id: String,
+    },
+}
+
+/// Builds `astrobox://account/<provider>/<id>` with both segments
+/// percent-encoded.
+pub fn account_link(provider: &str, account_id: &str) -> String {
+    format!(
+        "{ACCOUNT_LINK_SCHEME}://{ACCOUNT_LINK_HOST}/{}/{}",
+        utf8_percent_encode(provider, NON_ALPHANUMERIC),
+        utf8_percent_encode(account_id, NON_ALPHANUMERIC)
+    )
+}
+
+/// Splits an account link into `(provider, account_id)`.
+pub fn parse_account_link(link: &str) -> Result<(String, String)> {
+    let url = Url::parse(link.trim()).with_context(|| format!("invalid account link {link}"))?;
+    if url.scheme() != ACCOUNT_LINK_SCHEME || url.host_str() != Some(ACCOUNT_LINK_HOST) {
+        return Err(anyhow!("not an account link: {link}"));
+    }
+    let segments: Vec<&str> = url
+        .path_segments()
+        .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+        .unwrap_or_default();
+    let [provider, account_id] = segments.as_slice() else {
+        return Err(anyhow!(
+            "account link must look like astrobox://account/<provider>/<id>: {link}"
+        ));
+    };
+    let decode = |segment: &str| {
+        percent_decode_str(segment)
+            .decode_utf8()
+            .map(|decoded| decoded.into_owned())
+            .with_context(|| format!("invalid percent-encoding in account link {link}"))
+    };
+    Ok((decode(provider)?, decode(account_id)?))
+}
+
+/// Resolves an account link against the provider registry. Malformed links
+/// are errors; unknown providers and missing accounts are reported through
+/// `ResolvedAccount`.
+pub async fn resolve_account_link(link: &str) -> Result<ResolvedAccount> {
+    let (provider_name, account_id) = parse_account_link(link)?;
+    let Some(provider) = get_account_provider(&provider_name).await else {
+        return Ok(ResolvedAccount::UnknownProvider {
+            provider: provider_name,
+        });
+    };
+    match provider.get_account(&account_id).await? {
+        Some(account) => Ok(ResolvedAccount::Found {
+            provider: provider_name,
+            account,
+        }),
+        None => Ok(ResolvedAccount::AccountNotFound {
+            provider: provider_name,
+            account_id,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_links_round_trip_with_encoded_segments() {
+        let link = account_link("xiaomi", "user@example.com");
+        assert_eq!(link, "astrobox://account/xiaomi/user%40example%2Ecom");
+        assert_eq!(
+            parse_account_link(&link).unwrap(),
+            ("xiaomi".to_string(), "user@example.com".to_string())
+        );
+    }
+
+    #[test]
+    fn malformed_account_links_are_rejected() {
+        for link in [
+            "https://account/xiaomi/1",
+            "astrobox://device/xiaomi/1",
+            "astrobox://account/xiaomi",
+            "astrobox://account/xiaomi/1/extra",
+            "not a link",
+        ] {
+            assert!(parse_account_link(link).is_err(), "{link}");
+        }
+    }
+}
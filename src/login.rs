@@ -0,0 +1,195 @@
+use crate::AccountProvider;
+use crate::models::AccountRecord;
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+
+/// Conversion from a provider's login response into the record it stores.
+/// Providers implement this for their response types so the mapping lives in
+/// one place and can be unit-tested without a login round trip.
+pub trait LoginResponseMapper {
+    fn to_account_record(self) -> Result<AccountRecord>;
+}
+
+/// Maps the response and stores it through `upsert_account`, which
+/// validates it first.
+pub async fn upsert_login_response<M: LoginResponseMapper>(
+    provider: &dyn AccountProvider,
+    response: M,
+) -> Result<AccountRecord> {
+    let account = response
+        .to_account_record()
+        .with_context(|| format!("map {} login response", provider.provider_name()))?;
+    provider.upsert_account(account).await
+}
+
+/// Declarative `LoginResponseMapper` for JSON responses, using JSON pointers
+/// (`"/data/openId"`). Numbers are accepted wherever a string is expected, so
+/// numeric user ids work as record ids.
+#[derive(Debug, Clone, Default)]
+pub struct LoginFieldMapping {
+    id: String,
+    names: Vec<String>,
+    avatar: Option<String>,
+    token: Option<String>,
+    extra: Vec<(String, String)>,
+}
+
+impl LoginFieldMapping {
+    pub fn new(id_pointer: impl Into<String>) -> Self {
+        Self {
+            id: id_pointer.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Candidate for the display name; the first non-empty candidate wins.
+    pub fn name(mut self, pointer: impl Into<String>) -> Self {
+        self.names.push(pointer.into());
+        self
+    }
+
+    pub fn avatar(mut self, pointer: impl Into<String>) -> Self {
+        self.avatar = Some(pointer.into());
+        self
+    }
+
+    pub fn token(mut self, pointer: impl Into<String>) -> Self {
+        self.token = Some(pointer.into());
+        self
+    }
+
+    /// Copies the value at `pointer` as-is into `extra[key]` when present.
+    pub fn extra(mut self, key: impl Into<String>, pointer: impl Into<String>) -> Self {
+        self.extra.push((key.into(), pointer.into()));
+        self
+    }
+
+    pub fn map(&self, response: &Value) -> Result<AccountRecord> {
+        let id = string_at(response, &self.id)
+            .ok_or_else(|| anyhow!("login response has no account id at {}", self.id))?;
+        let name = self
+            .names
+            .iter()
+            .find_map(|pointer| string_at(response, pointer))
+            .unwrap_or_default();
+        let optional = |pointer: &Option<String>| {
+            pointer
+                .as_deref()
+                .and_then(|pointer| string_at(response, pointer))
+        };
+        let mut record = AccountRecord::new(id, name)
+            .with_avatar(optional(&self.avatar))
+            .with_token(optional(&self.token));
+        for (key, pointer) in &self.extra {
+            if let Some(value) = response.pointer(pointer).filter(|value| !value.is_null()) {
+                record.set_extra_value(key.clone(), value.clone());
+            }
+        }
+        Ok(record)
+    }
+
+    /// Pairs the mapping with a response so it can go through
+    /// `upsert_login_response`.
+    pub fn bind(&self, response: Value) -> MappedLoginResponse<'_> {
+        MappedLoginResponse {
+            mapping: self,
+            response,
+        }
+    }
+}
+
+pub struct MappedLoginResponse<'a> {
+    mapping: &'a LoginFieldMapping,
+    response: Value,
+}
+
+impl LoginResponseMapper for MappedLoginResponse<'_> {
+    fn to_account_record(self) -> Result<AccountRecord> {
+        self.mapping.map(&self.response)
+    }
+}
+
+/// Trimmed, non-empty string (or number) at `pointer`.
+fn string_at(response: &Value, pointer: &str) -> Option<String> {
+    match response.pointer(pointer)? {
+        Value::String(value) => Some(value.trim().to_string()).filter(|value| !value.is_empty()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AccountError;
+    use crate::testing::TestProvider;
+    use serde_json::json;
+
+    #[test]
+    fn field_mapping_reads_pointers_with_name_fallbacks() {
+        let mapping = LoginFieldMapping::new("/data/userId")
+            .name("/data/nickName")
+            .name("/data/userName")
+            .avatar("/data/avatar")
+            .token("/data/token")
+            .extra("email", "/data/email")
+            .extra("missing", "/data/missing");
+        let response = json!({
+            "data": {
+                "userId": 1024,
+                "nickName": "  ",
+                "userName": "alice",
+                "token": "t-1",
+                "email": "alice@example.com"
+            }
+        });
+
+        let record = mapping.bind(response).to_account_record().unwrap();
+        assert_eq!(record.id, "1024");
+        assert_eq!(record.name, "alice");
+        assert_eq!(record.avatar, None);
+        assert_eq!(record.token.as_deref(), Some("t-1"));
+        assert_eq!(
+            record.extra_value("email"),
+            Some(&json!("alice@example.com"))
+        );
+        assert_eq!(record.extra_value("missing"), None);
+
+        assert!(mapping.map(&json!({ "data": {} })).is_err());
+    }
+
+    #[tokio::test]
+    async fn login_responses_are_validated_before_storing() {
+        let provider = TestProvider::new("strict").requiring(&["email"]);
+        let mapping = LoginFieldMapping::new("/id").extra("email", "/email");
+
+        let err = upsert_login_response(&provider, mapping.bind(json!({ "id": "1" })))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AccountError>(),
+            Some(AccountError::MissingRequiredExtra { .. })
+        ));
+        assert!(provider.stored().is_empty());
+
+        let response = json!({ "id": "1", "email": "alice@example.com" });
+        upsert_login_response(&provider, mapping.bind(response))
+            .await
+            .unwrap();
+        assert_eq!(provider.stored().len(), 1);
+
+        let provider = TestProvider::new("bearer").token_prefix("Bearer ");
+        let mapping = LoginFieldMapping::new("/id").token("/token");
+        let err = upsert_login_response(
+            &provider,
+            mapping.bind(json!({ "id": "1", "token": "abc" })),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AccountError>(),
+            Some(AccountError::InvalidTokenFormat { .. })
+        ));
+        assert!(provider.stored().is_empty());
+    }
+}
@@ -1,17 +1,160 @@
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Strongly typed payload stored in `AccountRecord::extra` under `KEY`.
+pub trait AccountExtension: Serialize + DeserializeOwned {
+    const KEY: &'static str;
+}
+
+/// Strongly typed registry metadata attached to a provider under `KEY`. The
+/// key is part of `describe_subsystem`'s output, so it must stay stable.
+pub trait ProviderMetadataEntry: Serialize + DeserializeOwned {
+    const KEY: &'static str;
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(from = "AccountRecordRepr")]
 pub struct AccountRecord {
     pub id: String,
     pub name: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub avatar: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
+    /// Additional tokens keyed by purpose (`"refresh"`, a service scope...).
+    /// `token` remains the primary `"access"` token; see `token_for`.
+    /// Blobs that stored a bare string here read it as the `"access"` entry,
+    /// and as `token` unless they also stored one there.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tokens: HashMap<String, TokenEntry>,
     #[serde(default)]
     pub extra: Map<String, Value>,
+    /// Sealed records are read-only: `AccountStore` rejects upserts that would
+    /// change them, other than unsealing.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub sealed: bool,
+}
+
+/// Purpose under which `AccountRecord::token` is exposed by `token_for`.
+pub const PRIMARY_TOKEN_PURPOSE: &str = "access";
+
+/// Placeholder written over secrets by `AccountRecord::redacted`.
+pub const REDACTED: &str = "[redacted]";
+
+/// Deserializes from `{ "value", "expires_at"? }` or, for blobs written
+/// before expiries existed, a bare token string.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(from = "TokenEntryRepr")]
+pub struct TokenEntry {
+    pub value: String,
+    /// Unix milliseconds after which the token should not be used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TokenEntryRepr {
+    Bare(String),
+    Structured {
+        value: String,
+        #[serde(default)]
+        expires_at: Option<u64>,
+    },
+}
+
+impl From<TokenEntryRepr> for TokenEntry {
+    fn from(repr: TokenEntryRepr) -> Self {
+        match repr {
+            TokenEntryRepr::Bare(value) => Self::new(value),
+            TokenEntryRepr::Structured { value, expires_at } => Self { value, expires_at },
+        }
+    }
+}
+
+/// Accepted shapes of `AccountRecord::tokens`: the purpose map, or a legacy
+/// bare string holding the primary token.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TokensRepr {
+    Bare(String),
+    Map(HashMap<String, TokenEntry>),
+}
+
+/// Deserialized form of `AccountRecord`, which accepts the legacy `tokens`
+/// shape.
+#[derive(Deserialize)]
+struct AccountRecordRepr {
+    id: String,
+    name: String,
+    #[serde(default)]
+    avatar: Option<String>,
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    tokens: Option<TokensRepr>,
+    #[serde(default)]
+    extra: Map<String, Value>,
+    #[serde(default)]
+    sealed: bool,
+}
+
+impl From<AccountRecordRepr> for AccountRecord {
+    fn from(repr: AccountRecordRepr) -> Self {
+        let (token, tokens) = match repr.tokens {
+            None => (repr.token, HashMap::new()),
+            Some(TokensRepr::Bare(value)) => (
+                repr.token.or_else(|| Some(value.clone())),
+                HashMap::from([(PRIMARY_TOKEN_PURPOSE.to_string(), TokenEntry::new(value))]),
+            ),
+            Some(TokensRepr::Map(tokens)) => (repr.token, tokens),
+        };
+        Self {
+            id: repr.id,
+            name: repr.name,
+            avatar: repr.avatar,
+            token,
+            tokens,
+            extra: repr.extra,
+            sealed: repr.sealed,
+        }
+    }
+}
+
+impl TokenEntry {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self {
+            value: value.into(),
+            expires_at: None,
+        }
+    }
+
+    pub fn is_expired(&self, now_millis: u64) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= now_millis)
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn reset_unparseable<T: DeserializeOwned>(
+    object: &mut Map<String, Value>,
+    key: &'static str,
+    reset: &mut Vec<&'static str>,
+) {
+    let parseable = object
+        .get(key)
+        .is_none_or(|value| serde_json::from_value::<T>(value.clone()).is_ok());
+    if !parseable {
+        object.remove(key);
+        reset.push(key);
+    }
 }
 
 impl AccountRecord {
@@ -21,7 +164,9 @@ impl AccountRecord {
             name: name.into(),
             avatar: None,
             token: None,
+            tokens: HashMap::new(),
             extra: Map::new(),
+            sealed: false,
         }
     }
 
@@ -35,6 +180,73 @@ impl AccountRecord {
         self
     }
 
+    /// Token stored for `purpose`. `"access"` falls back to the primary
+    /// `token` so records written before per-purpose tokens keep working.
+    pub fn token_for(&self, purpose: &str) -> Option<&str> {
+        self.tokens
+            .get(purpose)
+            .map(|entry| entry.value.as_str())
+            .or_else(|| {
+                (purpose == PRIMARY_TOKEN_PURPOSE)
+                    .then_some(self.token.as_deref())
+                    .flatten()
+            })
+    }
+
+    /// Stores a token for `purpose`. Setting `"access"` also updates the
+    /// primary `token`, so the two never disagree.
+    pub fn set_token_for(
+        &mut self,
+        purpose: impl Into<String>,
+        entry: TokenEntry,
+    ) -> Option<TokenEntry> {
+        let purpose = purpose.into();
+        if purpose == PRIMARY_TOKEN_PURPOSE {
+            self.token = Some(entry.value.clone());
+        }
+        self.tokens.insert(purpose, entry)
+    }
+
+    /// Removes the token for `purpose`; removing `"access"` clears `token`.
+    pub fn remove_token_for(&mut self, purpose: &str) -> Option<TokenEntry> {
+        if purpose == PRIMARY_TOKEN_PURPOSE {
+            self.token = None;
+        }
+        self.tokens.remove(purpose)
+    }
+
+    /// Copy that is safe to log or display: `token` and every entry of
+    /// `tokens` are replaced with `REDACTED` (expiries are kept). Secrets
+    /// placed in `extra` by callers are not touched.
+    pub fn redacted(&self) -> AccountRecord {
+        let mut redacted = self.clone();
+        if let Some(token) = redacted.token.as_mut() {
+            *token = REDACTED.to_string();
+        }
+        for entry in redacted.tokens.values_mut() {
+            entry.value = REDACTED.to_string();
+        }
+        redacted
+    }
+
+    /// Credentials carried in the fields `redacted` covers. Values that are
+    /// empty or already `REDACTED`, and an `"access"` entry repeating
+    /// `token`, are not counted.
+    pub fn secret_count(&self) -> usize {
+        let is_secret = |value: &str| !value.is_empty() && value != REDACTED;
+        let primary = self.token.as_deref().filter(|token| is_secret(token));
+        let purposes = self
+            .tokens
+            .iter()
+            .filter(|(purpose, entry)| {
+                is_secret(&entry.value)
+                    && !(purpose.as_str() == PRIMARY_TOKEN_PURPOSE
+                        && primary == Some(entry.value.as_str()))
+            })
+            .count();
+        usize::from(primary.is_some()) + purposes
+    }
+
     pub fn extra_value(&self, key: &str) -> Option<&Value> {
         self.extra.get(key)
     }
@@ -51,6 +263,181 @@ impl AccountRecord {
     pub fn remove_extra(&mut self, key: &str) -> Option<Value> {
         self.extra.remove(key)
     }
+
+    /// Reads a nested `extra` value by dotted path, e.g. `"prefs.theme"`.
+    pub fn extra_path(&self, path: &str) -> Option<&Value> {
+        let mut segments = path.split('.');
+        let mut current = self.extra.get(segments.next()?)?;
+        for segment in segments {
+            current = current.as_object()?.get(segment)?;
+        }
+        Some(current)
+    }
+
+    /// Writes a nested `extra` value by dotted path, creating intermediate
+    /// objects as needed, and returns the value it replaced. Fails without
+    /// modifying the record if a segment is empty or an intermediate value
+    /// exists but is not an object.
+    pub fn set_extra_path(&mut self, path: &str, value: Value) -> anyhow::Result<Option<Value>> {
+        let segments: Vec<&str> = path.split('.').collect();
+        if segments.iter().any(|segment| segment.is_empty()) {
+            anyhow::bail!("invalid extra path {path:?}: empty segment");
+        }
+        let (last, parents) = segments.split_last().expect("split yields a segment");
+        for depth in 1..=parents.len() {
+            let prefix = segments[..depth].join(".");
+            if let Some(existing) = self.extra_path(&prefix)
+                && !existing.is_object()
+            {
+                anyhow::bail!("extra path {path:?}: {prefix:?} exists but is not an object");
+            }
+        }
+        let mut current = &mut self.extra;
+        for segment in parents {
+            current = current
+                .entry(segment.to_string())
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .expect("intermediate segments checked above");
+        }
+        Ok(current.insert(last.to_string(), value))
+    }
+
+    pub fn get_extension<T: AccountExtension>(&self) -> Option<T> {
+        self.extra_as(T::KEY)
+    }
+
+    pub fn set_extension<T: AccountExtension>(&mut self, value: &T) -> serde_json::Result<()> {
+        self.set_extra_value(T::KEY, serde_json::to_value(value)?);
+        Ok(())
+    }
+
+    pub fn remove_extension<T: AccountExtension>(&mut self) -> Option<T> {
+        self.remove_extra(T::KEY)
+            .and_then(|value| serde_json::from_value(value).ok())
+    }
+
+    /// Lenient counterpart of `serde_json::from_value`: optional fields that
+    /// fail to parse are reset to their defaults instead of failing the whole
+    /// record, and their names are returned alongside it. A record whose `id`
+    /// is unusable still fails.
+    pub fn from_value_tolerant(value: Value) -> serde_json::Result<(Self, Vec<&'static str>)> {
+        let Value::Object(mut object) = value else {
+            return serde_json::from_value(value).map(|record| (record, Vec::new()));
+        };
+        let mut reset = Vec::new();
+        if object.get("name").is_some_and(|name| !name.is_string()) {
+            object.insert("name".to_string(), Value::String(String::new()));
+            reset.push("name");
+        }
+        reset_unparseable::<Option<String>>(&mut object, "avatar", &mut reset);
+        reset_unparseable::<Option<String>>(&mut object, "token", &mut reset);
+        reset_unparseable::<Option<TokensRepr>>(&mut object, "tokens", &mut reset);
+        reset_unparseable::<Map<String, Value>>(&mut object, "extra", &mut reset);
+        reset_unparseable::<bool>(&mut object, "sealed", &mut reset);
+        serde_json::from_value(Value::Object(object)).map(|record| (record, reset))
+    }
+
+    /// Keys from `required` that are absent from `extra` or set to `null`.
+    pub fn missing_extra_keys(&self, required: &[&str]) -> Vec<String> {
+        required
+            .iter()
+            .filter(|key| self.extra_value(key).is_none_or(Value::is_null))
+            .map(|key| key.to_string())
+            .collect()
+    }
+
+    /// Equality as far as callers can observe it. Every field is compared,
+    /// but `extra` (and any object nested inside it) is compared as an
+    /// unordered set of entries in which a `null` value counts as absent, so
+    /// `{"a": 1, "b": null}` equals `{"a": 1}`. Arrays still compare in order
+    /// and `avatar`/`token` of `None` differ from `Some("")`.
+    pub fn semantically_eq(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.name == other.name
+            && self.avatar == other.avatar
+            && self.token == other.token
+            && self.tokens == other.tokens
+            && self.sealed == other.sealed
+            && objects_semantically_eq(&self.extra, &other.extra)
+    }
+}
+
+fn objects_semantically_eq(left: &Map<String, Value>, right: &Map<String, Value>) -> bool {
+    let covers = |a: &Map<String, Value>, b: &Map<String, Value>| {
+        a.iter().all(|(key, value)| match b.get(key) {
+            Some(other) => values_semantically_eq(value, other),
+            None => value.is_null(),
+        })
+    };
+    covers(left, right) && covers(right, left)
+}
+
+fn values_semantically_eq(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Object(left), Value::Object(right)) => objects_semantically_eq(left, right),
+        (Value::Array(left), Value::Array(right)) => {
+            left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right)
+                    .all(|(left, right)| values_semantically_eq(left, right))
+        }
+        _ => left == right,
+    }
+}
+
+/// UI-oriented description of one editable `AccountRecord` field. `name` is
+/// the serialized key; `secret` fields should be rendered as masked inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldDescriptor {
+    pub name: &'static str,
+    pub field_type: FieldType,
+    pub secret: bool,
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    Text,
+    Url,
+    Json,
+}
+
+impl FieldDescriptor {
+    const fn new(name: &'static str, field_type: FieldType) -> Self {
+        Self {
+            name,
+            field_type,
+            secret: false,
+            required: false,
+        }
+    }
+
+    const fn secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
+
+    const fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+}
+
+impl AccountRecord {
+    pub fn form_descriptor() -> Vec<FieldDescriptor> {
+        vec![
+            FieldDescriptor::new("id", FieldType::Text).required(),
+            FieldDescriptor::new("name", FieldType::Text).required(),
+            FieldDescriptor::new("avatar", FieldType::Url),
+            FieldDescriptor::new("token", FieldType::Text).secret(),
+            FieldDescriptor::new("tokens", FieldType::Json).secret(),
+            FieldDescriptor::new("extra", FieldType::Json),
+        ]
+    }
 }
 
 impl Default for AccountRecord {
@@ -60,7 +447,794 @@ impl Default for AccountRecord {
             name: String::new(),
             avatar: None,
             token: None,
+            tokens: HashMap::new(),
             extra: Map::new(),
+            sealed: false,
+        }
+    }
+}
+
+/// Canonical provider identity used by the registry. Declare built-in
+/// providers once as `const` values with `ProviderId::from_static` (or convert
+/// an app enum through `From`) instead of repeating string literals; plain
+/// `&str`/`String` still convert for dynamic names.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ProviderId(Cow<'static, str>);
+
+impl ProviderId {
+    pub const fn from_static(name: &'static str) -> Self {
+        Self(Cow::Borrowed(name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ProviderId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for ProviderId {
+    fn from(name: &str) -> Self {
+        Self(Cow::Owned(name.to_string()))
+    }
+}
+
+impl From<&String> for ProviderId {
+    fn from(name: &String) -> Self {
+        Self(Cow::Owned(name.clone()))
+    }
+}
+
+impl From<String> for ProviderId {
+    fn from(name: String) -> Self {
+        Self(Cow::Owned(name))
+    }
+}
+
+impl From<&ProviderId> for ProviderId {
+    fn from(id: &ProviderId) -> Self {
+        id.clone()
+    }
+}
+
+impl PartialEq<str> for ProviderId {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<String> for ProviderId {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+/// One changed field in a `ChangeSet`. Top-level fields use their serialized
+/// name (`"name"`, `"token"`); entries of `extra` are `"extra.<key>"`. An
+/// absent value is `null`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// Field-level difference between two versions of one record.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ChangeSet {
+    pub account_id: String,
+    pub changes: Vec<FieldChange>,
+}
+
+impl ChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Copy with `token` and `tokens` values replaced by `REDACTED`; a token
+    /// change still shows up, just not its value.
+    pub fn redacted(&self) -> ChangeSet {
+        let mut redacted = self.clone();
+        for change in &mut redacted.changes {
+            redact_field_value(&change.field, &mut change.old);
+            redact_field_value(&change.field, &mut change.new);
+        }
+        redacted
+    }
+
+    /// Applies the `new` side of every change to `record`, the same way a
+    /// client patches its local copy.
+    pub fn apply_to(&self, record: &AccountRecord) -> serde_json::Result<AccountRecord> {
+        let mut fields = record_fields(record);
+        let mut extra = record.extra.clone();
+        for change in &self.changes {
+            let (target, key) = match change.field.strip_prefix("extra.") {
+                Some(key) => (&mut extra, key),
+                None => (&mut fields, change.field.as_str()),
+            };
+            if change.new.is_null() {
+                target.remove(key);
+            } else {
+                target.insert(key.to_string(), change.new.clone());
+            }
+        }
+        fields.insert("extra".to_string(), Value::Object(extra));
+        serde_json::from_value(Value::Object(fields))
+    }
+}
+
+impl AccountRecord {
+    /// Fields that differ between `self` and `newer`, compared on their
+    /// serialized form with `null` and absent treated alike (see
+    /// `semantically_eq`). `id` is not compared; the set carries `newer.id`.
+    pub fn diff(&self, newer: &AccountRecord) -> ChangeSet {
+        let mut changes = Vec::new();
+        let old = record_fields(self);
+        let new = record_fields(newer);
+        diff_objects(&old, &new, "", &["id", "extra"], &mut changes);
+        diff_objects(&self.extra, &newer.extra, "extra.", &[], &mut changes);
+        ChangeSet {
+            account_id: newer.id.clone(),
+            changes,
+        }
+    }
+
+    /// Sparse `field -> value` pairs for what changed since `baseline`, for
+    /// targeted updates. Fields are named as in `diff` (`"extra.<key>"` for
+    /// `extra` entries) and a cleared field maps to `null`. Token values are
+    /// plaintext; log the output of `redact_changed_fields` instead.
+    pub fn changed_fields_since(&self, baseline: &AccountRecord) -> Vec<(String, Value)> {
+        baseline
+            .diff(self)
+            .changes
+            .into_iter()
+            .map(|change| (change.field, change.new))
+            .collect()
+    }
+}
+
+/// Copy of `changed_fields_since` output with `token` and every `tokens`
+/// value replaced by `REDACTED`, as `AccountRecord::redacted` does.
+pub fn redact_changed_fields(fields: &[(String, Value)]) -> Vec<(String, Value)> {
+    fields
+        .iter()
+        .map(|(field, value)| {
+            let mut value = value.clone();
+            redact_field_value(field, &mut value);
+            (field.clone(), value)
+        })
+        .collect()
+}
+
+fn redact_field_value(field: &str, value: &mut Value) {
+    match (field, value) {
+        ("token", Value::String(token)) => *token = REDACTED.to_string(),
+        ("tokens", Value::Object(tokens)) => {
+            for entry in tokens.values_mut() {
+                match entry {
+                    Value::Object(entry) => {
+                        entry.insert("value".to_string(), Value::from(REDACTED));
+                    }
+                    other => *other = Value::from(REDACTED),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn record_fields(record: &AccountRecord) -> Map<String, Value> {
+    match serde_json::to_value(record) {
+        Ok(Value::Object(fields)) => fields,
+        _ => unreachable!("AccountRecord serializes as an object"),
+    }
+}
+
+fn diff_objects(
+    old: &Map<String, Value>,
+    new: &Map<String, Value>,
+    prefix: &str,
+    skip: &[&str],
+    changes: &mut Vec<FieldChange>,
+) {
+    let keys: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        if skip.contains(&key.as_str()) {
+            continue;
+        }
+        let old = old.get(key).cloned().unwrap_or(Value::Null);
+        let new = new.get(key).cloned().unwrap_or(Value::Null);
+        if !values_semantically_eq(&old, &new) {
+            changes.push(FieldChange {
+                field: format!("{prefix}{key}"),
+                old,
+                new,
+            });
+        }
+    }
+}
+
+/// The "same" identity as seen by several providers. `members` pairs each
+/// provider name with its record.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UnifiedAccount {
+    pub key: String,
+    pub members: Vec<(String, AccountRecord)>,
+}
+
+/// Default grouping key for unified accounts: `extra["email"]`, trimmed and
+/// lowercased.
+pub fn email_grouping_key(account: &AccountRecord) -> Option<String> {
+    account
+        .extra_value("email")
+        .and_then(Value::as_str)
+        .map(|email| email.trim().to_lowercase())
+        .filter(|email| !email.is_empty())
+}
+
+/// Buckets `accounts` by the value at `extra[key]`. Strings are used as-is,
+/// numbers and booleans are stringified (`3`, `true`); records without the
+/// key, or with `null`, an array or an object there, go to `default_bucket`.
+/// Order within a bucket follows `accounts`.
+pub fn group_by_extra(
+    accounts: impl IntoIterator<Item = AccountRecord>,
+    key: &str,
+    default_bucket: &str,
+) -> BTreeMap<String, Vec<AccountRecord>> {
+    let mut groups: BTreeMap<String, Vec<AccountRecord>> = BTreeMap::new();
+    for account in accounts {
+        let bucket = match account.extra_value(key) {
+            Some(Value::String(value)) => value.clone(),
+            Some(value @ (Value::Number(_) | Value::Bool(_))) => value.to_string(),
+            _ => default_bucket.to_string(),
+        };
+        groups.entry(bucket).or_default().push(account);
+    }
+    groups
+}
+
+/// `extra` key holding a record's tags, an array of strings.
+pub const TAGS_KEY: &str = "tags";
+
+/// One test of an `AccountQuery`. Public so remote providers can translate a
+/// query into their own filter instead of fetching everything.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum AccountCondition {
+    /// `extra["tags"]` contains the tag.
+    Tag { tag: String },
+    /// The primary token is set and not expired at `now_millis`.
+    TokenValid { now_millis: u64 },
+    /// `extra[key]` equals `value`.
+    ExtraEq { key: String, value: Value },
+}
+
+impl AccountCondition {
+    pub fn matches(&self, account: &AccountRecord) -> bool {
+        match self {
+            Self::Tag { tag } => account
+                .extra_value(TAGS_KEY)
+                .and_then(Value::as_array)
+                .is_some_and(|tags| tags.iter().any(|t| t.as_str() == Some(tag.as_str()))),
+            Self::TokenValid { now_millis } => {
+                account.token_for(PRIMARY_TOKEN_PURPOSE).is_some()
+                    && !account
+                        .tokens
+                        .get(PRIMARY_TOKEN_PURPOSE)
+                        .is_some_and(|entry| entry.is_expired(*now_millis))
+            }
+            Self::ExtraEq { key, value } => account.extra_value(key) == Some(value),
+        }
+    }
+}
+
+/// Composable account filter; a record matches when it passes every
+/// condition, so an empty query matches everything.
+///
+/// `AccountQuery::new().tag("work").token_valid(now).extra_eq("region", "cn")`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountQuery {
+    conditions: Vec<AccountCondition>,
+}
+
+impl AccountQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tag(self, tag: impl Into<String>) -> Self {
+        self.and(AccountCondition::Tag { tag: tag.into() })
+    }
+
+    pub fn token_valid(self, now_millis: u64) -> Self {
+        self.and(AccountCondition::TokenValid { now_millis })
+    }
+
+    pub fn extra_eq(self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.and(AccountCondition::ExtraEq {
+            key: key.into(),
+            value: value.into(),
+        })
+    }
+
+    pub fn and(mut self, condition: AccountCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    pub fn conditions(&self) -> &[AccountCondition] {
+        &self.conditions
+    }
+
+    pub fn matches(&self, account: &AccountRecord) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(account))
+    }
+
+    /// The query as a closure, for `Iterator::filter` and the like.
+    pub fn predicate(&self) -> impl Fn(&AccountRecord) -> bool + '_ {
+        move |account| self.matches(account)
+    }
+
+    /// The matching records, in their original order.
+    pub fn apply(&self, accounts: impl IntoIterator<Item = AccountRecord>) -> Vec<AccountRecord> {
+        accounts
+            .into_iter()
+            .filter(|account| self.matches(account))
+            .collect()
+    }
+}
+
+/// Quotas a provider knows about. `None` means unlimited or unknown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderLimits {
+    #[serde(default)]
+    pub max_accounts: Option<u32>,
+    #[serde(default)]
+    pub accounts_used: Option<u32>,
+    #[serde(default)]
+    pub max_devices: Option<u32>,
+    #[serde(default)]
+    pub devices_used: Option<u32>,
+}
+
+impl ProviderLimits {
+    /// True only when both the limit and the usage are known and the limit is
+    /// reached.
+    pub fn accounts_exhausted(&self) -> bool {
+        matches!((self.max_accounts, self.accounts_used), (Some(max), Some(used)) if used >= max)
+    }
+}
+
+/// How a provider presents itself in pickers such as the account chooser.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderMetadata {
+    pub display_name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderOp {
+    Refresh,
+    ListAccounts,
+    GetAccount,
+    UpsertAccount,
+    RemoveAccount,
+}
+
+/// Optional behaviour a provider can declare through
+/// `AccountProvider::capabilities`, so callers can check for it up front
+/// instead of calling and catching an error or a silent default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// `refresh_token` is implemented.
+    TokenRefresh,
+    /// `verify_ownership` actually checks the proof.
+    OwnershipVerification,
+    /// `health_check` probes the backend.
+    HealthCheck,
+    /// `limits` reports real quotas.
+    Limits,
+    /// `list_accounts_capped` limits the fetch itself, not just the result.
+    CappedListing,
+    /// `validate_credentials` checks credentials with the backend.
+    CredentialValidation,
+    /// `metadata_localized` translates for some locales.
+    LocalizedMetadata,
+    /// `list_accounts_query` filters on the backend.
+    ServerSideQuery,
+}
+
+impl Capability {
+    /// `AccountProvider` API version that introduced the capability. A
+    /// provider reporting an older `api_version` is never considered to
+    /// support it, whatever it declares.
+    pub fn since_api_version(self) -> u32 {
+        match self {
+            Self::TokenRefresh
+            | Self::OwnershipVerification
+            | Self::HealthCheck
+            | Self::Limits
+            | Self::CappedListing => 1,
+            Self::CredentialValidation => 2,
+            Self::LocalizedMetadata => 3,
+            Self::ServerSideQuery => 4,
+        }
+    }
+}
+
+/// Advisory hint for how costly a provider operation is, so the UI can decide
+/// between running it inline, showing a spinner or asking for confirmation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationCost {
+    /// Served from local state only.
+    #[default]
+    Free,
+    /// Contacts a remote server.
+    Network,
+    /// Contacts a remote server and is slow or rate limited.
+    Expensive,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn form_descriptor_covers_every_serialized_field() {
+        let mut record = AccountRecord::new("1", "Alice")
+            .with_avatar(Some("https://example.com/a.png".to_string()))
+            .with_token(Some("secret".to_string()));
+        record.set_token_for("refresh", TokenEntry::new("refresh-secret"));
+        let serialized = serde_json::to_value(&record).unwrap();
+        let serialized = serialized.as_object().unwrap();
+        let descriptor = AccountRecord::form_descriptor();
+
+        assert_eq!(descriptor.len(), serialized.len());
+        for field in &descriptor {
+            assert!(serialized.contains_key(field.name), "{}", field.name);
         }
+        let secrets: Vec<_> = descriptor
+            .iter()
+            .filter(|field| field.secret)
+            .map(|field| field.name)
+            .collect();
+        assert_eq!(secrets, vec!["token", "tokens"]);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct DeviceBinding {
+        did: String,
+        model: String,
+    }
+
+    impl AccountExtension for DeviceBinding {
+        const KEY: &'static str = "deviceBinding";
+    }
+
+    #[test]
+    fn extensions_round_trip_under_their_key() {
+        let binding = DeviceBinding {
+            did: "blt.1".to_string(),
+            model: "miwear.watch.o62".to_string(),
+        };
+        let mut record = AccountRecord::new("1", "Alice");
+        assert_eq!(record.get_extension::<DeviceBinding>(), None);
+
+        record.set_extension(&binding).unwrap();
+        assert_eq!(
+            record.extra_value("deviceBinding"),
+            Some(&json!({ "did": "blt.1", "model": "miwear.watch.o62" }))
+        );
+        assert_eq!(record.get_extension::<DeviceBinding>(), Some(binding));
+        assert!(record.remove_extension::<DeviceBinding>().is_some());
+        assert!(record.extra.is_empty());
+    }
+
+    #[test]
+    fn tolerant_parse_resets_only_malformed_fields() {
+        let (record, reset) = AccountRecord::from_value_tolerant(json!({
+            "id": "1",
+            "name": "Alice",
+            "avatar": 42,
+            "token": "secret",
+            "extra": ["not", "a", "map"],
+        }))
+        .unwrap();
+
+        assert_eq!(reset, vec!["avatar", "extra"]);
+        assert_eq!(
+            record,
+            AccountRecord::new("1", "Alice").with_token(Some("secret".to_string()))
+        );
+        assert!(AccountRecord::from_value_tolerant(json!({ "id": 1, "name": "Alice" })).is_err());
+    }
+
+    #[test]
+    fn missing_extra_keys_treats_null_as_absent() {
+        let mut record = AccountRecord::new("1", "Alice");
+        record.set_extra_value("region", json!("cn"));
+        record.set_extra_value("plan", Value::Null);
+
+        assert_eq!(
+            record.missing_extra_keys(&["region", "plan", "email"]),
+            vec!["plan".to_string(), "email".to_string()]
+        );
+        assert!(record.missing_extra_keys(&[]).is_empty());
+    }
+
+    #[test]
+    fn changed_fields_are_sparse_and_redactable() {
+        let mut baseline = AccountRecord::new("1", "Alice");
+        baseline.set_extra_value("region", json!("cn"));
+        baseline.set_extra_value("plan", json!("free"));
+        let mut current = baseline.clone().with_token("secret".to_string());
+        current.set_token_for("refresh", TokenEntry::new("refresh-secret"));
+        current.set_extra_value("plan", json!("pro"));
+        current.extra.remove("region");
+
+        let changed = current.changed_fields_since(&baseline);
+        let fields: Vec<_> = changed.iter().map(|(field, _)| field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec!["token", "tokens", "extra.plan", "extra.region"]
+        );
+        assert_eq!(changed[2].1, json!("pro"));
+        assert_eq!(changed[3].1, Value::Null);
+        assert!(baseline.changed_fields_since(&baseline).is_empty());
+
+        let logged = serde_json::to_string(&redact_changed_fields(&changed)).unwrap();
+        assert!(!logged.contains("secret"));
+        assert!(logged.contains("pro"));
+    }
+
+    #[test]
+    fn queries_require_every_condition() {
+        let mut work = AccountRecord::new("1", "work").with_token("t".to_string());
+        work.set_extra_value(TAGS_KEY, json!(["work", "vip"]));
+        work.set_extra_value("region", json!("cn"));
+        let mut expired = work.clone();
+        expired.id = "2".to_string();
+        expired.set_token_for(
+            PRIMARY_TOKEN_PURPOSE,
+            TokenEntry {
+                value: "t".to_string(),
+                expires_at: Some(100),
+            },
+        );
+        let mut abroad = work.clone();
+        abroad.id = "3".to_string();
+        abroad.set_extra_value("region", json!("eu"));
+        let untagged = AccountRecord::new("4", "home").with_token("t".to_string());
+        let accounts = vec![work, expired, abroad, untagged];
+
+        let ids = |query: AccountQuery| -> Vec<String> {
+            query
+                .apply(accounts.clone())
+                .into_iter()
+                .map(|account| account.id)
+                .collect()
+        };
+        assert_eq!(ids(AccountQuery::new()).len(), 4);
+        assert_eq!(ids(AccountQuery::new().tag("work")), ["1", "2", "3"]);
+        assert_eq!(ids(AccountQuery::new().token_valid(200)), ["1", "3", "4"]);
+        assert_eq!(
+            ids(AccountQuery::new()
+                .tag("work")
+                .token_valid(200)
+                .extra_eq("region", "cn")),
+            ["1"]
+        );
+    }
+
+    #[test]
+    fn grouping_by_extra_stringifies_scalars_and_defaults_the_rest() {
+        let with = |id: &str, value: Option<Value>| {
+            let mut account = AccountRecord::new(id, id);
+            if let Some(value) = value {
+                account.set_extra_value("plan", value);
+            }
+            account
+        };
+        let groups = group_by_extra(
+            vec![
+                with("1", Some(json!("pro"))),
+                with("2", Some(json!(3))),
+                with("3", None),
+                with("4", Some(json!(["pro"]))),
+                with("5", Some(json!("pro"))),
+            ],
+            "plan",
+            "none",
+        );
+        let ids: BTreeMap<_, Vec<_>> = groups
+            .iter()
+            .map(|(bucket, accounts)| {
+                (
+                    bucket.as_str(),
+                    accounts.iter().map(|account| account.id.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ids,
+            BTreeMap::from([
+                ("3", vec!["2"]),
+                ("none", vec!["3", "4"]),
+                ("pro", vec!["1", "5"])
+            ])
+        );
+    }
+
+    #[test]
+    fn legacy_bare_token_strings_read_as_structured_tokens() {
+        let structured: AccountRecord = serde_json::from_value(json!({
+            "id": "1",
+            "name": "Alice",
+            "tokens": { "access": { "value": "abc" } },
+        }))
+        .unwrap();
+        let bare_map: AccountRecord = serde_json::from_value(json!({
+            "id": "1",
+            "name": "Alice",
+            "tokens": "abc",
+        }))
+        .unwrap();
+        let bare_entry: AccountRecord = serde_json::from_value(json!({
+            "id": "1",
+            "name": "Alice",
+            "tokens": { "access": "abc" },
+        }))
+        .unwrap();
+        assert_eq!(bare_entry, structured);
+        assert_eq!(structured.tokens["access"], TokenEntry::new("abc"));
+        assert_eq!(bare_map, structured.clone().with_token("abc".to_string()));
+        assert_eq!(
+            bare_map.token.as_deref(),
+            bare_map.token_for(PRIMARY_TOKEN_PURPOSE)
+        );
+
+        let (tolerant, reset) = AccountRecord::from_value_tolerant(json!({
+            "id": "1",
+            "name": "Alice",
+            "tokens": "abc",
+        }))
+        .unwrap();
+        assert!(reset.is_empty());
+        assert_eq!(tolerant, bare_map);
+
+        let with_primary: AccountRecord = serde_json::from_value(json!({
+            "id": "1",
+            "name": "Alice",
+            "token": "primary",
+            "tokens": "abc",
+        }))
+        .unwrap();
+        assert_eq!(with_primary.token.as_deref(), Some("primary"));
+    }
+
+    #[test]
+    fn purpose_tokens_fall_back_to_primary_and_redact_fully() {
+        let mut record = AccountRecord::new("1", "Alice").with_token("legacy".to_string());
+        assert_eq!(record.token_for(PRIMARY_TOKEN_PURPOSE), Some("legacy"));
+        assert_eq!(record.token_for("refresh"), None);
+
+        let refresh = TokenEntry {
+            value: "r-1".to_string(),
+            expires_at: Some(1_000),
+        };
+        record.set_token_for("refresh", refresh.clone());
+        record.set_token_for("access", TokenEntry::new("a-2"));
+        assert_eq!(record.token.as_deref(), Some("a-2"));
+        assert_eq!(record.token_for("refresh"), Some("r-1"));
+        assert!(refresh.is_expired(1_000) && !refresh.is_expired(999));
+
+        let redacted = record.redacted();
+        assert_eq!(redacted.token.as_deref(), Some(REDACTED));
+        assert!(
+            redacted
+                .tokens
+                .values()
+                .all(|entry| entry.value == REDACTED)
+        );
+        assert_eq!(redacted.tokens["refresh"].expires_at, Some(1_000));
+
+        record.remove_token_for("access");
+        assert_eq!(record.token_for("access"), None);
+    }
+
+    #[test]
+    fn extra_paths_create_intermediates_and_reject_non_objects() {
+        let mut record = AccountRecord::new("1", "Alice");
+        assert_eq!(
+            record.set_extra_path("prefs.theme", json!("dark")).unwrap(),
+            None
+        );
+        assert_eq!(
+            record
+                .set_extra_path("prefs.theme", json!("light"))
+                .unwrap(),
+            Some(json!("dark"))
+        );
+        assert_eq!(record.extra_path("prefs.theme"), Some(&json!("light")));
+        assert_eq!(record.extra_path("prefs.missing"), None);
+
+        record.set_extra_value("region", json!("cn"));
+        let err = record
+            .set_extra_path("region.city", json!("x"))
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("\"region\" exists but is not an object")
+        );
+        assert!(record.set_extra_path("prefs..theme", json!(1)).is_err());
+        assert_eq!(record.extra_value("region"), Some(&json!("cn")));
+    }
+
+    #[test]
+    fn semantic_equality_ignores_key_order_and_null_entries() {
+        let mut stored = AccountRecord::new("1", "Alice");
+        stored.extra = serde_json::from_str(
+            r#"{"region":"cn","plan":null,"profile":{"vip":true,"nick":null}}"#,
+        )
+        .unwrap();
+        let mut built = AccountRecord::new("1", "Alice");
+        built.set_extra_value("profile", json!({ "vip": true }));
+        built.set_extra_value("region", json!("cn"));
+
+        assert!(stored.semantically_eq(&built));
+        assert!(built.semantically_eq(&stored));
+        assert!(!stored.semantically_eq(&built.clone().with_token(String::new())));
+
+        built.set_extra_value("plan", json!("pro"));
+        assert!(!stored.semantically_eq(&built));
+    }
+
+    #[test]
+    fn diff_lists_changed_fields_and_patches_back() {
+        let mut old = AccountRecord::new("1", "Alice").with_token("t1".to_string());
+        old.set_extra_value("blob", json!({ "large": [1, 2, 3] }));
+        old.set_extra_value("region", json!("cn"));
+        let mut new = old.clone().with_token(None);
+        new.name = "Alice W".to_string();
+        new.set_extra_value("region", json!("eu"));
+
+        let changes = old.diff(&new);
+        let fields: Vec<_> = changes.changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["name", "token", "extra.region"]);
+        assert_eq!(changes.changes[1].new, Value::Null);
+        assert!(changes.apply_to(&old).unwrap().semantically_eq(&new));
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn none_fields_are_omitted_and_explicit_nulls_still_parse() {
+        let record = AccountRecord::new("1", "Alice");
+        let value = serde_json::to_value(&record).unwrap();
+        assert_eq!(value, json!({ "id": "1", "name": "Alice", "extra": {} }));
+        assert_eq!(
+            serde_json::from_value::<AccountRecord>(value).unwrap(),
+            record
+        );
+
+        let legacy =
+            json!({ "id": "1", "name": "Alice", "avatar": null, "token": null, "extra": {} });
+        assert_eq!(
+            serde_json::from_value::<AccountRecord>(legacy).unwrap(),
+            record
+        );
     }
 }
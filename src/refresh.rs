@@ -0,0 +1,403 @@
+use crate::decorator::ProviderDecorator;
+use crate::models::{AccountRecord, ProviderId};
+use crate::storage::now_millis;
+use crate::{AccountProvider, get_account_provider, registered_providers};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type SharedRefresh = Shared<BoxFuture<'static, Result<(), Arc<anyhow::Error>>>>;
+
+/// Wraps a provider so concurrent `refresh` calls share one in-flight
+/// operation (single-flight). Callers that arrive while a refresh is running
+/// await the same result; once it settles the slot is cleared, so a failure
+/// only reaches the callers that were waiting on it and the next call starts
+/// a fresh refresh. Every other method is passed straight through.
+pub struct DedupRefreshProvider {
+    inner: Arc<dyn AccountProvider>,
+    in_flight: Mutex<Option<SharedRefresh>>,
+}
+
+impl DedupRefreshProvider {
+    pub fn new(inner: Arc<dyn AccountProvider>) -> Self {
+        Self {
+            inner,
+            in_flight: Mutex::new(None),
+        }
+    }
+
+    pub fn inner(&self) -> &Arc<dyn AccountProvider> {
+        &self.inner
+    }
+
+    fn join_or_start(&self) -> SharedRefresh {
+        let mut slot = self.in_flight.lock().unwrap();
+        if let Some(refresh) = slot.as_ref() {
+            return refresh.clone();
+        }
+        let inner = Arc::clone(&self.inner);
+        let refresh = async move { inner.refresh().await.map_err(Arc::new) }
+            .boxed()
+            .shared();
+        *slot = Some(refresh.clone());
+        refresh
+    }
+}
+
+#[async_trait]
+impl ProviderDecorator for DedupRefreshProvider {
+    fn decorated(&self) -> &dyn AccountProvider {
+        self.inner.as_ref()
+    }
+
+    async fn on_refresh(&self) -> anyhow::Result<()> {
+        let refresh = self.join_or_start();
+        let result = refresh.clone().await;
+        {
+            // Only the operation we awaited may be cleared; a newer one may
+            // already have taken its place.
+            let mut slot = self.in_flight.lock().unwrap();
+            if slot
+                .as_ref()
+                .is_some_and(|current| current.ptr_eq(&refresh))
+            {
+                *slot = None;
+            }
+        }
+        result.map_err(|err| anyhow!("{err:#}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefreshSchedulerConfig {
+    /// Check interval for providers without a `refresh_interval` hint.
+    pub interval: Duration,
+    /// Tokens expiring within this window from now are refreshed.
+    pub expiry_window: Duration,
+    /// Upper bound of the doubling back-off applied after failed passes.
+    pub max_backoff: Duration,
+}
+
+impl Default for RefreshSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5 * 60),
+            expiry_window: Duration::from_secs(10 * 60),
+            max_backoff: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// Running refresh scheduler; the task stops on `cancel` or when the handle
+/// is dropped.
+pub struct RefreshSchedulerHandle {
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+impl RefreshSchedulerHandle {
+    pub fn cancel(self) {
+        drop(self);
+    }
+}
+
+impl Drop for RefreshSchedulerHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Default)]
+struct ProviderSchedule {
+    next_due: Option<Instant>,
+    failures: u32,
+}
+
+/// Background task that, for every registered provider with
+/// `supports_refresh`, periodically refreshes accounts whose tokens expire
+/// within `config.expiry_window` and stores the results with
+/// `upsert_account` (which emits the usual change events). Providers are
+/// checked every `refresh_interval` (or `config.interval`); a pass with any
+/// failure backs that provider off exponentially up to `config.max_backoff`.
+/// Providers registered later are picked up on the next wake-up.
+pub fn spawn_refresh_scheduler(config: RefreshSchedulerConfig) -> RefreshSchedulerHandle {
+    let task = tauri::async_runtime::spawn(async move {
+        let mut schedules: HashMap<ProviderId, ProviderSchedule> = HashMap::new();
+        loop {
+            let mut next_wake = Instant::now() + config.interval;
+            for provider in registered_providers() {
+                if !provider.supports_refresh() {
+                    continue;
+                }
+                let interval = provider.refresh_interval().unwrap_or(config.interval);
+                let schedule = schedules.entry(provider.provider_id()).or_default();
+                if let Some(due) = schedule.next_due
+                    && Instant::now() < due
+                {
+                    next_wake = next_wake.min(due);
+                    continue;
+                }
+                let delay = match refresh_expiring_tokens(provider.as_ref(), config).await {
+                    Ok(()) => {
+                        schedule.failures = 0;
+                        interval
+                    }
+                    Err(err) => {
+                        schedule.failures = schedule.failures.saturating_add(1);
+                        log::warn!(
+                            "[AccountRefresh] {} refresh failed ({} in a row): {err:#}",
+                            provider.provider_name(),
+                            schedule.failures
+                        );
+                        backoff_delay(interval, schedule.failures, config.max_backoff)
+                    }
+                };
+                let due = Instant::now() + delay;
+                schedule.next_due = Some(due);
+                next_wake = next_wake.min(due);
+            }
+            tokio::time::sleep_until(next_wake.into()).await;
+        }
+    });
+    RefreshSchedulerHandle { task }
+}
+
+/// Refreshes every expiring account of `provider`; keeps going after a
+/// failed account and reports the first error.
+async fn refresh_expiring_tokens(
+    provider: &dyn AccountProvider,
+    config: RefreshSchedulerConfig,
+) -> anyhow::Result<()> {
+    let accounts = provider.list_accounts().await?;
+    let mut first_error = None;
+    for account in expiring_accounts(&accounts, now_millis(), config.expiry_window) {
+        let refreshed = match provider.refresh_token(account).await {
+            Ok(refreshed) => provider.upsert_account(refreshed).await.map(|_| ()),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = refreshed {
+            first_error.get_or_insert(err.context(format!("refresh account {}", account.id)));
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Refreshes running at once in `rotate_all_tokens`.
+const ROTATE_CONCURRENCY: usize = 4;
+
+/// Outcome of `rotate_all_tokens`, as account ids.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateReport {
+    pub rotated: Vec<String>,
+    /// Accounts whose refresh or upsert failed, with the reason.
+    pub failed: Vec<(String, String)>,
+    /// Accounts without a `"refresh"` token, or every account when the
+    /// provider does not support refreshing.
+    pub skipped: Vec<String>,
+}
+
+/// Forces a `refresh_token` + `upsert_account` for every account of
+/// `provider_name` that has a `"refresh"` token, e.g. after a security
+/// incident. At most `ROTATE_CONCURRENCY` refreshes run at once; one failed
+/// account does not stop the others. Fails only when the provider is not
+/// registered or its accounts cannot be listed.
+pub async fn rotate_all_tokens(provider_name: &str) -> anyhow::Result<RotateReport> {
+    let provider = get_account_provider(provider_name)
+        .await
+        .ok_or_else(|| anyhow!("provider {provider_name} is not registered"))?;
+    rotate_tokens(provider.as_ref()).await
+}
+
+async fn rotate_tokens(provider: &dyn AccountProvider) -> anyhow::Result<RotateReport> {
+    let accounts = provider.list_accounts().await?;
+    let supported = provider.supports_refresh();
+    let (rotatable, skipped): (Vec<_>, Vec<_>) = accounts
+        .into_iter()
+        .partition(|account| supported && account.token_for("refresh").is_some());
+    let mut report = RotateReport {
+        skipped: skipped.into_iter().map(|account| account.id).collect(),
+        ..RotateReport::default()
+    };
+    let mut results = futures_util::stream::iter(rotatable)
+        .map(|account| async move {
+            let rotated = match provider.refresh_token(&account).await {
+                Ok(refreshed) => provider.upsert_account(refreshed).await.map(|_| ()),
+                Err(err) => Err(err),
+            };
+            (account.id, rotated)
+        })
+        .buffer_unordered(ROTATE_CONCURRENCY);
+    while let Some((account_id, rotated)) = results.next().await {
+        match rotated {
+            Ok(()) => report.rotated.push(account_id),
+            Err(err) => report.failed.push((account_id, format!("{err:#}"))),
+        }
+    }
+    report.rotated.sort();
+    report.failed.sort();
+    Ok(report)
+}
+
+fn expiring_accounts(
+    accounts: &[AccountRecord],
+    now_millis: u64,
+    window: Duration,
+) -> impl Iterator<Item = &AccountRecord> {
+    let deadline = now_millis.saturating_add(window.as_millis() as u64);
+    accounts.iter().filter(move |account| {
+        account
+            .tokens
+            .values()
+            .any(|entry| entry.is_expired(deadline))
+    })
+}
+
+fn backoff_delay(base: Duration, failures: u32, max: Duration) -> Duration {
+    let factor = 2u32.saturating_pow(failures.min(16));
+    base.saturating_mul(factor).min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PRIMARY_TOKEN_PURPOSE, TokenEntry};
+    use crate::testing::TestProvider;
+    use futures_util::future::join_all;
+    use std::sync::atomic::Ordering;
+
+    #[tokio::test]
+    async fn concurrent_refreshes_share_one_call_and_failures_do_not_stick() {
+        let inner = Arc::new(TestProvider::new("slow").gated(true));
+        let dedup = DedupRefreshProvider::new(inner.clone());
+
+        let callers = join_all((0..10).map(|_| dedup.refresh()));
+        let release = async {
+            inner.gate().release.notify_one();
+        };
+        let (results, ()) = futures_util::join!(callers, release);
+        assert_eq!(inner.refreshes.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|result| result.is_err()));
+
+        inner.gate().release.notify_one();
+        dedup.refresh().await.unwrap();
+        assert_eq!(inner.refreshes.load(Ordering::SeqCst), 2);
+    }
+
+    fn rotating() -> TestProvider {
+        TestProvider::new("rotating")
+            .refreshable(&["bad"])
+            .with_accounts(["a", "bad", "b", "plain"].into_iter().map(|id| {
+                let mut account = AccountRecord::new(id, id);
+                if id != "plain" {
+                    account.set_token_for("refresh", TokenEntry::new("r"));
+                }
+                account
+            }))
+    }
+
+    #[tokio::test]
+    async fn rotated_records_are_validated_before_storing() {
+        let provider = rotating().requiring(&["region"]);
+        let report = rotate_tokens(&provider).await.unwrap();
+        assert!(report.rotated.is_empty());
+        assert_eq!(report.failed.len(), 3);
+        assert!(report.failed[0].1.contains("region"));
+        assert!(
+            provider
+                .stored()
+                .iter()
+                .all(|account| account.token.is_none())
+        );
+    }
+
+    #[tokio::test]
+    async fn refreshed_tokens_must_match_the_provider_format() {
+        let provider = rotating().token_prefix("Bearer ");
+        let report = rotate_tokens(&provider).await.unwrap();
+        assert!(report.rotated.is_empty());
+        assert!(
+            report
+                .failed
+                .iter()
+                .any(|(id, reason)| id == "a" && reason.contains("Bearer"))
+        );
+        assert!(
+            provider
+                .stored()
+                .iter()
+                .all(|account| account.token.is_none())
+        );
+
+        let mut expiring = AccountRecord::new("1", "Alice");
+        expiring.set_token_for(
+            PRIMARY_TOKEN_PURPOSE,
+            TokenEntry {
+                value: "Bearer old".to_string(),
+                expires_at: Some(0),
+            },
+        );
+        let provider = TestProvider::new("strict_refresh")
+            .refreshable(&[])
+            .token_prefix("Bearer ")
+            .with_accounts([expiring]);
+        let config = RefreshSchedulerConfig::default();
+        assert!(refresh_expiring_tokens(&provider, config).await.is_err());
+        assert_eq!(provider.stored()[0].token.as_deref(), Some("Bearer old"));
+    }
+
+    #[tokio::test]
+    async fn rotation_reports_each_account() {
+        let provider = rotating();
+        let report = rotate_tokens(&provider).await.unwrap();
+        assert_eq!(report.rotated, vec!["a", "b"]);
+        assert_eq!(report.skipped, vec!["plain"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "bad");
+        assert!(report.failed[0].1.contains("revoked"));
+
+        let stored = provider.stored();
+        assert_eq!(stored[0].token.as_deref(), Some("rotated"));
+        assert_eq!(stored[1].token, None);
+
+        let report = rotate_tokens(&TestProvider::new("static")).await.unwrap();
+        assert_eq!(report, RotateReport::default());
+    }
+
+    #[test]
+    fn scheduler_picks_tokens_inside_the_window_and_backs_off() {
+        let mut soon = AccountRecord::new("1", "Soon");
+        soon.set_token_for(
+            "access",
+            TokenEntry {
+                value: "a".to_string(),
+                expires_at: Some(10_000 + 60_000),
+            },
+        );
+        let mut later = AccountRecord::new("2", "Later");
+        later.set_token_for(
+            "access",
+            TokenEntry {
+                value: "b".to_string(),
+                expires_at: Some(10_000 + 3_600_000),
+            },
+        );
+        let untracked = AccountRecord::new("3", "No expiry").with_token("c".to_string());
+        let accounts = [soon, later, untracked];
+
+        let picked: Vec<_> = expiring_accounts(&accounts, 10_000, Duration::from_secs(600))
+            .map(|account| account.id.as_str())
+            .collect();
+        assert_eq!(picked, vec!["1"]);
+
+        let base = Duration::from_secs(60);
+        let max = Duration::from_secs(600);
+        assert_eq!(backoff_delay(base, 1, max), Duration::from_secs(120));
+        assert_eq!(backoff_delay(base, 3, max), Duration::from_secs(480));
+        assert_eq!(backoff_delay(base, 40, max), max);
+    }
+}
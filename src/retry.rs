@@ -0,0 +1,125 @@
+use crate::AccountProvider;
+use crate::error::AccountError;
+use std::future::Future;
+
+/// Whether `provider` considers `err` worth retrying. The error is mapped
+/// with `AccountError::classify`, so reqwest timeouts and statuses reach the
+/// provider's hook as `Timeout` / `Http`; anything unrecognized is treated as
+/// permanent so it never turns into a retry storm.
+pub fn is_retryable_error(provider: &dyn AccountProvider, err: &anyhow::Error) -> bool {
+    AccountError::classify(err).is_some_and(|err| provider.is_retryable(&err))
+}
+
+/// Runs `operation` up to `max_attempts` times, retrying only while the
+/// provider classifies the failure as retryable. The last error is returned
+/// unchanged. Delays between attempts are left to `operation`.
+pub async fn retry_operation<T, F, Fut>(
+    provider: &dyn AccountProvider,
+    max_attempts: u32,
+    mut operation: F,
+) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < max_attempts && is_retryable_error(provider, &err) => {
+                log::warn!(
+                    "[AccountProvider] {} attempt {attempt}/{max_attempts} failed, retrying: {err:#}",
+                    provider.provider_name()
+                );
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestProvider;
+    use anyhow::Context;
+    use std::cell::Cell;
+
+    async fn timed_out() -> anyhow::Error {
+        tokio::time::timeout(std::time::Duration::ZERO, std::future::pending::<()>())
+            .await
+            .context("fetch account list")
+            .unwrap_err()
+    }
+
+    fn unauthorized() -> anyhow::Error {
+        anyhow::Error::new(AccountError::Http { status: 401 }).context("refresh token")
+    }
+
+    #[tokio::test]
+    async fn timeouts_are_retried_and_auth_failures_are_not() {
+        let provider = TestProvider::new("remote");
+        let calls = Cell::new(0);
+        let result = retry_operation(&provider, 3, || {
+            calls.set(calls.get() + 1);
+            let call = calls.get();
+            async move {
+                if call < 3 {
+                    Err(timed_out().await)
+                } else {
+                    Ok(call)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+
+        calls.set(0);
+        let result: anyhow::Result<()> = retry_operation(&provider, 3, || {
+            calls.set(calls.get() + 1);
+            async { Err(unauthorized()) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+
+        calls.set(0);
+        let result: anyhow::Result<()> = retry_operation(&provider, 2, || {
+            calls.set(calls.get() + 1);
+            async { Err(timed_out().await) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 2);
+
+        let untyped = anyhow::anyhow!("connection reset");
+        assert!(!is_retryable_error(&provider, &untyped));
+    }
+
+    #[tokio::test]
+    async fn maintenance_mode_is_not_retried() {
+        let provider = TestProvider::new("store_backed");
+        let calls = Cell::new(0);
+        let result: anyhow::Result<()> = retry_operation(&provider, 3, || {
+            calls.set(calls.get() + 1);
+            async {
+                Err(AccountError::MaintenanceMode {
+                    store: "account_provider_store_backed".to_string(),
+                }
+                .into())
+            }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn providers_can_reclassify_failures() {
+        let provider = TestProvider::new("no_timeouts")
+            .retryable(|err| !matches!(err, AccountError::Timeout) && err.is_transient());
+        assert!(!is_retryable_error(&provider, &timed_out().await));
+        let throttled = anyhow::Error::new(AccountError::Http { status: 429 });
+        assert!(is_retryable_error(&provider, &throttled));
+    }
+}
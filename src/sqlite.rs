@@ -0,0 +1,477 @@
+//! SQLite `StorageBackend` for desktop hosts that want indexed account
+//! storage instead of one JSON blob per store key.
+
+use crate::models::{AccountRecord, email_grouping_key};
+use crate::storage::StorageBackend;
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use tauri::AppHandle;
+
+/// Schema steps, applied in order; `PRAGMA user_version` is the number of
+/// steps already applied. Append new steps, never edit existing ones.
+const MIGRATIONS: &[&str] = &[
+    // 1: one row per store key for everything but its records, one row per
+    // record. `split` is 0 for values stored whole (layouts other than the
+    // envelope, or envelopes whose records have no unique string ids).
+    "CREATE TABLE stores (
+        key TEXT PRIMARY KEY NOT NULL,
+        value TEXT NOT NULL,
+        split INTEGER NOT NULL
+    );
+    CREATE TABLE accounts (
+        key TEXT NOT NULL REFERENCES stores (key) ON DELETE CASCADE,
+        id TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        email TEXT,
+        record TEXT NOT NULL,
+        PRIMARY KEY (key, id)
+    );
+    CREATE INDEX accounts_by_email ON accounts (email);",
+];
+
+/// Keeps each store key's envelope in SQLite, one row per record, indexed by
+/// id and by email (`email_grouping_key`). Writes only touch the rows whose
+/// record changed, and reads reassemble the envelope `from_stored_value`
+/// expects. Secrets are stored as the `AccountStore` wrote them, so use a
+/// `TokenCipher` to keep them encrypted at rest. Queries run synchronously
+/// on the calling task.
+pub struct SqliteBackend {
+    conn: Mutex<Connection>,
+}
+
+impl std::fmt::Debug for SqliteBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SqliteBackend").finish_non_exhaustive()
+    }
+}
+
+impl SqliteBackend {
+    /// Opens or creates the database at `path` and migrates it.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let conn = Connection::open(path)
+            .with_context(|| format!("open account database {}", path.display()))?;
+        Self::from_connection(conn)
+    }
+
+    /// A private database that lives as long as the backend.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Schema version of the open database.
+    pub fn schema_version(&self) -> Result<u32> {
+        schema_version(&self.conn())
+    }
+
+    /// The value stored under `key`, as `StorageBackend::get`.
+    pub fn read_value(&self, key: &str) -> Result<Option<Value>> {
+        let conn = self.conn();
+        let row: Option<(String, bool)> = conn
+            .query_row(
+                "SELECT value, split FROM stores WHERE key = ?1",
+                params![key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((value, split)) = row else {
+            return Ok(None);
+        };
+        let mut value: Value =
+            serde_json::from_str(&value).with_context(|| format!("parse stored value of {key}"))?;
+        if split {
+            let mut statement =
+                conn.prepare("SELECT record FROM accounts WHERE key = ?1 ORDER BY position")?;
+            let accounts = statement
+                .query_map(params![key], |row| row.get::<_, String>(0))?
+                .map(|record| Ok(serde_json::from_str(&record?)?))
+                .collect::<Result<Vec<Value>>>()
+                .with_context(|| format!("parse stored records of {key}"))?;
+            value
+                .as_object_mut()
+                .ok_or_else(|| anyhow!("stored envelope of {key} is not an object"))?
+                .insert("accounts".to_string(), Value::Array(accounts));
+        }
+        Ok(Some(value))
+    }
+
+    /// Stores `value` under `key`, as `StorageBackend::set`, in one
+    /// transaction. Rows of records that are unchanged and in the same
+    /// position are left alone.
+    pub fn write_value(&self, key: &str, value: &Value) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let (rest, records) = split_envelope(value);
+        let rest = serde_json::to_string(&rest)?;
+        let existing: Option<(String, bool)> = tx
+            .query_row(
+                "SELECT value, split FROM stores WHERE key = ?1",
+                params![key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let split = records.is_some();
+        if existing.as_ref() != Some(&(rest.clone(), split)) {
+            tx.execute(
+                "INSERT INTO stores (key, value, split) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value, split = excluded.split",
+                params![key, rest, split],
+            )?;
+        }
+
+        let mut stored: HashMap<String, (i64, String)> = HashMap::new();
+        {
+            let mut statement =
+                tx.prepare("SELECT id, position, record FROM accounts WHERE key = ?1")?;
+            let rows = statement.query_map(params![key], |row| {
+                Ok((row.get::<_, String>(0)?, (row.get(1)?, row.get(2)?)))
+            })?;
+            for row in rows {
+                let (id, row) = row?;
+                stored.insert(id, row);
+            }
+        }
+        let records = records.unwrap_or_default();
+        let kept: HashSet<&str> = records.iter().map(|(id, _)| *id).collect();
+        for id in stored.keys().filter(|id| !kept.contains(id.as_str())) {
+            tx.execute(
+                "DELETE FROM accounts WHERE key = ?1 AND id = ?2",
+                params![key, id],
+            )?;
+        }
+        for (position, (id, record)) in records.iter().enumerate() {
+            let position = position as i64;
+            let text = serde_json::to_string(record)?;
+            if stored.get(*id) == Some(&(position, text.clone())) {
+                continue;
+            }
+            tx.execute(
+                "INSERT INTO accounts (key, id, position, email, record)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (key, id) DO UPDATE SET position = excluded.position,
+                     email = excluded.email, record = excluded.record",
+                params![key, id, position, record_email(record), text],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Removes `key` and its records, as `StorageBackend::remove`.
+    pub fn remove_value(&self, key: &str) -> Result<()> {
+        self.conn()
+            .execute("DELETE FROM stores WHERE key = ?1", params![key])?;
+        Ok(())
+    }
+
+    /// Stored keys that start with `prefix`, sorted.
+    pub fn list_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        let conn = self.conn();
+        let mut statement = conn
+            .prepare("SELECT key FROM stores WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key")?;
+        let keys = statement
+            .query_map(params![prefix], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(keys)
+    }
+
+    /// The record with `id` in store `key`, as stored (secrets still
+    /// encrypted when the store has a cipher).
+    pub fn find_by_id(&self, key: &str, id: &str) -> Result<Option<AccountRecord>> {
+        let record: Option<String> = self
+            .conn()
+            .query_row(
+                "SELECT record FROM accounts WHERE key = ?1 AND id = ?2",
+                params![key, id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        record
+            .map(|record| serde_json::from_str(&record))
+            .transpose()
+            .with_context(|| format!("parse stored record {id} of {key}"))
+    }
+
+    /// Records in store `key` whose `extra["email"]` matches `email`, trimmed
+    /// and case-insensitively, in store order and as stored.
+    pub fn find_by_email(&self, key: &str, email: &str) -> Result<Vec<AccountRecord>> {
+        let email = email.trim().to_lowercase();
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT record FROM accounts WHERE key = ?1 AND email = ?2 ORDER BY position",
+        )?;
+        let records = statement
+            .query_map(params![key, email], |row| row.get::<_, String>(0))?
+            .map(|record| Ok(serde_json::from_str(&record?)?))
+            .collect::<Result<_>>()
+            .with_context(|| format!("parse stored records of {key}"))?;
+        Ok(records)
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl StorageBackend for SqliteBackend {
+    async fn get(&self, _app_handle: &AppHandle, key: &str) -> Result<Option<Value>> {
+        self.read_value(key)
+    }
+
+    async fn set(&self, _app_handle: &AppHandle, key: &str, value: &Value) -> Result<()> {
+        self.write_value(key, value)
+    }
+
+    async fn remove(&self, _app_handle: &AppHandle, key: &str) -> Result<()> {
+        self.remove_value(key)
+    }
+
+    async fn keys(&self, _app_handle: &AppHandle, prefix: &str) -> Result<Vec<String>> {
+        self.list_keys(prefix)
+    }
+}
+
+fn schema_version(conn: &Connection) -> Result<u32> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    u32::try_from(version).map_err(|_| anyhow!("unexpected account database version {version}"))
+}
+
+/// Applies the pending `MIGRATIONS`, each in its own transaction. A database
+/// migrated by a newer crate is refused rather than written to.
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let version = schema_version(conn)?;
+    let known = MIGRATIONS.len() as u32;
+    if version > known {
+        return Err(anyhow!(
+            "account database version {version} is newer than supported ({known})"
+        ));
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)
+            .with_context(|| format!("migrate account database to version {}", index + 1))?;
+        tx.pragma_update(None, "user_version", index as u32 + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Splits an envelope into everything but `accounts`, and its records keyed
+/// by id. Values that are not an envelope of uniquely identified records
+/// come back whole, without records.
+fn split_envelope(value: &Value) -> (Value, Option<Vec<(&str, &Value)>>) {
+    let Value::Object(object) = value else {
+        return (value.clone(), None);
+    };
+    let Some(Value::Array(accounts)) = object.get("accounts") else {
+        return (value.clone(), None);
+    };
+    let mut seen = HashSet::new();
+    let mut records = Vec::with_capacity(accounts.len());
+    for record in accounts {
+        match record.get("id").and_then(Value::as_str) {
+            Some(id) if seen.insert(id) => records.push((id, record)),
+            _ => return (value.clone(), None),
+        }
+    }
+    let rest: Map<String, Value> = object
+        .iter()
+        .filter(|(field, _)| field.as_str() != "accounts")
+        .map(|(field, value)| (field.clone(), value.clone()))
+        .collect();
+    (Value::Object(rest), Some(records))
+}
+
+fn record_email(record: &Value) -> Option<String> {
+    serde_json::from_value::<AccountRecord>(record.clone())
+        .ok()
+        .as_ref()
+        .and_then(email_grouping_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::from_stored_value;
+    use crate::storage::{AccountStore, StoreLayout};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn envelope(records: Value) -> Value {
+        json!({ "version": 1, "accounts": records, "active": "1" })
+    }
+
+    fn total_changes(backend: &SqliteBackend) -> u64 {
+        backend.conn().total_changes()
+    }
+
+    #[test]
+    fn migrates_new_databases_to_the_latest_version() {
+        let backend = SqliteBackend::open_in_memory().unwrap();
+        assert_eq!(backend.schema_version().unwrap(), MIGRATIONS.len() as u32);
+        // Re-running is a no-op once up to date.
+        migrate(&mut backend.conn()).unwrap();
+        assert_eq!(backend.schema_version().unwrap(), MIGRATIONS.len() as u32);
+    }
+
+    #[test]
+    fn refuses_databases_from_newer_versions() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() as u32 + 1)
+            .unwrap();
+        let err = SqliteBackend::from_connection(conn).unwrap_err();
+        assert!(err.to_string().contains("newer than supported"));
+    }
+
+    #[test]
+    fn envelopes_round_trip_through_record_rows() {
+        let backend = SqliteBackend::open_in_memory().unwrap();
+        let value = envelope(json!([
+            { "id": "2", "name": "b", "extra": {} },
+            { "id": "1", "name": "a", "extra": {} },
+        ]));
+        backend.write_value("account_provider_x", &value).unwrap();
+
+        let read = backend.read_value("account_provider_x").unwrap().unwrap();
+        assert_eq!(read, value);
+        let loaded = from_stored_value(read).unwrap();
+        assert_eq!(loaded.layout(), StoreLayout::Envelope);
+        let ids: Vec<_> = loaded.accounts().iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["2", "1"]);
+        assert_eq!(backend.read_value("account_provider_y").unwrap(), None);
+    }
+
+    #[test]
+    fn other_layouts_are_stored_whole() {
+        let backend = SqliteBackend::open_in_memory().unwrap();
+        let array = json!([{ "id": "1", "name": "a" }]);
+        let duplicates = envelope(json!([{ "id": "1" }, { "id": "1" }]));
+        backend.write_value("array", &array).unwrap();
+        backend.write_value("duplicates", &duplicates).unwrap();
+
+        assert_eq!(backend.read_value("array").unwrap(), Some(array));
+        assert_eq!(backend.read_value("duplicates").unwrap(), Some(duplicates));
+        assert_eq!(backend.find_by_id("array", "1").unwrap(), None);
+    }
+
+    #[test]
+    fn looks_records_up_by_id_and_email() {
+        let backend = SqliteBackend::open_in_memory().unwrap();
+        let value = envelope(json!([
+            { "id": "1", "name": "a", "extra": { "email": "Alice@Example.com " } },
+            { "id": "2", "name": "b", "extra": {} },
+            { "id": "3", "name": "c", "extra": { "email": "alice@example.com" } },
+        ]));
+        backend.write_value("k", &value).unwrap();
+
+        assert_eq!(backend.find_by_id("k", "2").unwrap().unwrap().name, "b");
+        assert_eq!(backend.find_by_id("k", "4").unwrap(), None);
+        let ids: Vec<_> = backend
+            .find_by_email("k", "ALICE@example.com")
+            .unwrap()
+            .into_iter()
+            .map(|account| account.id)
+            .collect();
+        assert_eq!(ids, ["1", "3"]);
+        assert!(
+            backend
+                .find_by_email("other", "alice@example.com")
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn writes_only_touch_changed_records() {
+        let backend = SqliteBackend::open_in_memory().unwrap();
+        let records = json!([
+            { "id": "1", "name": "a", "extra": {} },
+            { "id": "2", "name": "b", "extra": {} },
+            { "id": "3", "name": "c", "extra": {} },
+        ]);
+        backend.write_value("k", &envelope(records)).unwrap();
+
+        let before = total_changes(&backend);
+        backend
+            .write_value(
+                "k",
+                &envelope(json!([
+                    { "id": "1", "name": "a", "extra": {} },
+                    { "id": "2", "name": "renamed", "extra": {} },
+                    { "id": "3", "name": "c", "extra": {} },
+                ])),
+            )
+            .unwrap();
+        assert_eq!(total_changes(&backend) - before, 1);
+
+        let before = total_changes(&backend);
+        backend
+            .write_value(
+                "k",
+                &envelope(json!([
+                    { "id": "1", "name": "a", "extra": {} },
+                    { "id": "2", "name": "renamed", "extra": {} },
+                ])),
+            )
+            .unwrap();
+        assert_eq!(total_changes(&backend) - before, 1);
+        assert_eq!(backend.find_by_id("k", "3").unwrap(), None);
+    }
+
+    #[test]
+    fn removal_drops_the_key_and_its_records() {
+        let backend = SqliteBackend::open_in_memory().unwrap();
+        backend
+            .write_value(
+                "account_provider_a",
+                &envelope(json!([{ "id": "1", "name": "a" }])),
+            )
+            .unwrap();
+        backend
+            .write_value("account_provider_b", &Value::Null)
+            .unwrap();
+        backend
+            .write_value("user_7_account_provider_a", &json!([]))
+            .unwrap();
+        assert_eq!(
+            backend.list_keys("account_provider_").unwrap(),
+            ["account_provider_a", "account_provider_b"]
+        );
+
+        backend.remove_value("account_provider_a").unwrap();
+        assert_eq!(backend.read_value("account_provider_a").unwrap(), None);
+        assert_eq!(backend.find_by_id("account_provider_a", "1").unwrap(), None);
+        assert_eq!(backend.list_keys("").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn stores_can_be_kept_in_sqlite() {
+        let backend = Arc::new(SqliteBackend::open_in_memory().unwrap());
+        let store = AccountStore::new("sqlite").with_backend(backend.clone());
+        backend
+            .write_value(store.key(), &envelope(json!([{ "id": "1", "name": "a" }])))
+            .unwrap();
+        assert_eq!(
+            backend.list_keys("account_provider_").unwrap(),
+            [store.key().to_string()]
+        );
+        let value = backend.read_value(store.key()).unwrap().unwrap();
+        assert_eq!(from_stored_value(value).unwrap().accounts()[0].name, "a");
+    }
+}
@@ -1,14 +1,225 @@
-use crate::models::AccountRecord;
+use crate::error::AccountError;
+use crate::events::{AccountChange, AccountEvent, emit_account_event, subscribe_account_events};
+use crate::models::{AccountQuery, AccountRecord, FieldChange, group_by_extra};
 use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
 use frontbridge::invoke_frontend;
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use futures_util::{Stream, stream};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tauri::AppHandle;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
 
 const METHOD_STORAGE_GET_JSON: &str = "host/storage/local/get_json";
 const METHOD_STORAGE_SET_JSON: &str = "host/storage/local/set_json";
 const METHOD_STORAGE_REMOVE: &str = "host/storage/local/remove";
+const METHOD_STORAGE_KEYS: &str = "host/storage/local/keys";
+
+/// Prefix of every key written by `AccountStore::new`.
+pub const ACCOUNT_STORE_KEY_PREFIX: &str = "account_provider_";
+
+/// Prefix of keys written by `AccountStore::for_user`; such keys look like
+/// `user_<uid>_account_provider_<name>`.
+pub const USER_STORE_KEY_PREFIX: &str = "user_";
+
+/// Suggested `max_tries` for `AccountStore::create_account_unique`.
+pub const DEFAULT_ID_COLLISION_TRIES: usize = 3;
+
+/// `extra` keys maintained by `AccountStore::record_login`.
+pub const LAST_USED_KEY: &str = "last_used";
+pub const UPDATED_AT_KEY: &str = "updated_at";
+pub const LOGIN_COUNT_KEY: &str = "login_count";
+
+static MAINTENANCE_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Process-global switch that makes every `AccountStore` in the process refuse
+/// regular writes (`upsert_account`, `remove_account(s_where)`, `clear`,
+/// counters, sealing, `SingleAccountStore::save`) with
+/// `AccountError::MaintenanceMode`, while reads keep working. Maintenance
+/// operations (`compact`, `reconcile`, `encrypt_existing_tokens`,
+/// `clear_tombstones`) stay available so they can run without racing other
+/// writers. Writes already holding the store lock finish normally.
+pub fn set_maintenance_mode(enabled: bool) {
+    MAINTENANCE_MODE.store(enabled, Ordering::SeqCst);
+}
+
+pub fn is_maintenance_mode() -> bool {
+    MAINTENANCE_MODE.load(Ordering::SeqCst)
+}
+
+/// Consecutive bridge failures that open the circuit breaker.
+const BRIDGE_FAILURE_THRESHOLD: u32 = 5;
+/// How long an open breaker rejects calls before letting one probe through.
+const BRIDGE_COOLDOWN: Duration = Duration::from_secs(10);
+
+static BRIDGE_BREAKER: Mutex<BridgeBreaker> = Mutex::new(BridgeBreaker::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeBreakerState {
+    /// Calls go through.
+    Closed,
+    /// Calls fail fast with `AccountError::BridgeUnavailable`.
+    Open,
+    /// The cooldown has passed; the next call probes the bridge.
+    HalfOpen,
+}
+
+/// Failure tracking for `invoke_frontend`, shared by every storage call in
+/// the process. After `BRIDGE_FAILURE_THRESHOLD` consecutive failures the
+/// breaker opens for `BRIDGE_COOLDOWN`, then admits a single probe: success
+/// closes it, failure reopens it for another cooldown.
+struct BridgeBreaker {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    /// Start of the in-flight probe; a probe whose caller was dropped counts
+    /// as abandoned after a cooldown.
+    probing: Option<Instant>,
+}
+
+impl BridgeBreaker {
+    const fn new() -> Self {
+        Self {
+            consecutive_failures: 0,
+            opened_at: None,
+            probing: None,
+        }
+    }
+
+    fn state(&self, now: Instant) -> BridgeBreakerState {
+        match self.opened_at {
+            None => BridgeBreakerState::Closed,
+            Some(opened_at) if now.duration_since(opened_at) < BRIDGE_COOLDOWN => {
+                BridgeBreakerState::Open
+            }
+            Some(_) => BridgeBreakerState::HalfOpen,
+        }
+    }
+
+    /// Admits a call, or returns how long until the breaker half-opens.
+    fn admit(&mut self, now: Instant) -> std::result::Result<(), Duration> {
+        match self.state(now) {
+            BridgeBreakerState::Closed => Ok(()),
+            BridgeBreakerState::HalfOpen
+                if self
+                    .probing
+                    .is_none_or(|started| now.duration_since(started) >= BRIDGE_COOLDOWN) =>
+            {
+                self.probing = Some(now);
+                Ok(())
+            }
+            BridgeBreakerState::HalfOpen => Err(Duration::ZERO),
+            BridgeBreakerState::Open => Err(self
+                .opened_at
+                .map(|opened_at| BRIDGE_COOLDOWN.saturating_sub(now.duration_since(opened_at)))
+                .unwrap_or_default()),
+        }
+    }
+
+    fn record(&mut self, succeeded: bool, now: Instant) {
+        let probing = self.probing.take().is_some();
+        if succeeded {
+            self.consecutive_failures = 0;
+            self.opened_at = None;
+            return;
+        }
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if probing || self.consecutive_failures >= BRIDGE_FAILURE_THRESHOLD {
+            self.opened_at = Some(now);
+        }
+    }
+}
+
+/// Current state of the frontend bridge circuit breaker.
+pub fn bridge_breaker_state() -> BridgeBreakerState {
+    BRIDGE_BREAKER.lock().unwrap().state(Instant::now())
+}
+
+/// `invoke_frontend` behind the bridge circuit breaker. Only transport
+/// failures count; a frontend that answers with `success: false` is up.
+async fn invoke_bridge<P, R>(app_handle: &AppHandle, method: &str, payload: P) -> Result<R>
+where
+    P: Serialize,
+    R: DeserializeOwned,
+{
+    if let Err(retry_after) = BRIDGE_BREAKER.lock().unwrap().admit(Instant::now()) {
+        return Err(AccountError::BridgeUnavailable { retry_after }.into());
+    }
+    let result = invoke_frontend(app_handle, method, payload).await;
+    BRIDGE_BREAKER
+        .lock()
+        .unwrap()
+        .record(result.is_ok(), Instant::now());
+    result
+}
+
+type SharedLoad =
+    Shared<BoxFuture<'static, std::result::Result<Option<Value>, Arc<anyhow::Error>>>>;
+
+static LOAD_FLIGHTS: OnceLock<Mutex<HashMap<String, LoadFlight>>> = OnceLock::new();
+
+/// Single-flight state of the bridge reads of one store key. Every write
+/// through `AccountStore` bumps `generation`, so a read started before the
+/// write is never joined by a caller arriving after it.
+#[derive(Default)]
+struct LoadFlight {
+    generation: u64,
+    in_flight: Option<(u64, SharedLoad)>,
+}
+
+impl LoadFlight {
+    fn join_or_start(&mut self, start: impl FnOnce() -> SharedLoad) -> SharedLoad {
+        if let Some((generation, load)) = &self.in_flight
+            && *generation == self.generation
+        {
+            return load.clone();
+        }
+        let load = start();
+        self.in_flight = Some((self.generation, load.clone()));
+        load
+    }
+
+    fn settle(&mut self, load: &SharedLoad) {
+        if self
+            .in_flight
+            .as_ref()
+            .is_some_and(|(_, current)| current.ptr_eq(load))
+        {
+            self.in_flight = None;
+        }
+    }
+
+    fn invalidate(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.in_flight = None;
+    }
+}
+
+fn load_flights() -> std::sync::MutexGuard<'static, HashMap<String, LoadFlight>> {
+    LOAD_FLIGHTS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap()
+}
+
+/// Rebuilds an error shared between several awaiters, keeping a typed
+/// `AccountError` reachable through `downcast_ref`.
+fn clone_shared_error(err: &anyhow::Error) -> anyhow::Error {
+    match err.downcast_ref::<AccountError>() {
+        Some(typed) => anyhow::Error::new(typed.clone()).context(format!("{err:#}")),
+        None => anyhow!("{err:#}"),
+    }
+}
+
+static STORE_LOCKS: OnceLock<Mutex<HashMap<String, Arc<AsyncMutex<()>>>>> = OnceLock::new();
 
 #[derive(Serialize)]
 struct LocalStorageKeyPayload<'a> {
@@ -21,9 +232,50 @@ struct LocalStorageSetPayload<'a> {
     value: Value,
 }
 
+#[derive(Serialize)]
+struct LocalStorageKeysPayload<'a> {
+    prefix: &'a str,
+}
+
+/// Frontend reply to storage writes. Failures may carry a machine-readable
+/// `code` (see `LocalStorageAcknowledge::into_result`) and a `message`.
 #[derive(Deserialize)]
 struct LocalStorageAcknowledge {
     success: bool,
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+impl LocalStorageAcknowledge {
+    /// Maps a failed ack to an `AccountError`: `quota_exceeded`,
+    /// `serialization_error` and `not_ready` have their own variants, any
+    /// other code becomes `AccountError::Frontend`. A failure without a code
+    /// stays an untyped error.
+    fn into_result(self, operation: &str, key: &str) -> Result<()> {
+        if self.success {
+            return Ok(());
+        }
+        let message = self.message.unwrap_or_default();
+        let Some(code) = self.code else {
+            return Err(anyhow!(
+                "frontend rejected localStorage {operation} for key {key}"
+            ));
+        };
+        let err = match code.as_str() {
+            "quota_exceeded" => AccountError::StorageQuotaExceeded {
+                key: key.to_string(),
+            },
+            "serialization_error" => AccountError::StorageSerialization {
+                key: key.to_string(),
+                message,
+            },
+            "not_ready" => AccountError::FrontendNotReady,
+            _ => AccountError::Frontend { code, message },
+        };
+        Err(anyhow::Error::new(err).context(format!("localStorage {operation} {key}")))
+    }
 }
 
 pub async fn local_storage_get_json<T>(
@@ -35,7 +287,7 @@ where
 {
     let key = key.as_ref();
     let payload = LocalStorageKeyPayload { key };
-    let value: Option<Value> = invoke_frontend(app_handle, METHOD_STORAGE_GET_JSON, payload)
+    let value: Option<Value> = invoke_bridge(app_handle, METHOD_STORAGE_GET_JSON, payload)
         .await
         .with_context(|| format!("localStorage get_json {}", key))?;
     if let Some(value) = value {
@@ -61,113 +313,2951 @@ where
         value: serde_json::to_value(data)
             .with_context(|| format!("serialize localStorage value for key {key}"))?,
     };
-    let ack: LocalStorageAcknowledge =
-        invoke_frontend(app_handle, METHOD_STORAGE_SET_JSON, payload)
-            .await
-            .with_context(|| format!("localStorage set_json {}", key))?;
-    if ack.success {
-        Ok(())
-    } else {
-        Err(anyhow!(
-            "frontend rejected localStorage set_json for key {key}"
-        ))
-    }
+    let ack: LocalStorageAcknowledge = invoke_bridge(app_handle, METHOD_STORAGE_SET_JSON, payload)
+        .await
+        .with_context(|| format!("localStorage set_json {}", key))?;
+    ack.into_result("set_json", key)
 }
 
 pub async fn local_storage_remove(app_handle: &AppHandle, key: impl AsRef<str>) -> Result<()> {
     let key = key.as_ref();
     let payload = LocalStorageKeyPayload { key };
-    let ack: LocalStorageAcknowledge = invoke_frontend(app_handle, METHOD_STORAGE_REMOVE, payload)
+    let ack: LocalStorageAcknowledge = invoke_bridge(app_handle, METHOD_STORAGE_REMOVE, payload)
         .await
         .with_context(|| format!("localStorage remove {}", key))?;
-    if ack.success {
-        Ok(())
-    } else {
-        Err(anyhow!(
-            "frontend rejected localStorage remove for key {key}"
-        ))
-    }
+    ack.into_result("remove", key)
 }
 
-#[derive(Debug, Clone)]
-pub struct AccountStore {
-    key: String,
+/// Keys currently in localStorage that start with `prefix` (`""` lists all).
+/// Requires a host that implements `host/storage/local/keys`.
+pub async fn local_storage_keys(app_handle: &AppHandle, prefix: &str) -> Result<Vec<String>> {
+    let payload = LocalStorageKeysPayload { prefix };
+    let keys: Vec<String> = invoke_bridge(app_handle, METHOD_STORAGE_KEYS, payload)
+        .await
+        .with_context(|| format!("localStorage keys {prefix}"))?;
+    Ok(keys
+        .into_iter()
+        .filter(|key| key.starts_with(prefix))
+        .collect())
 }
 
-impl AccountStore {
-    pub fn new(provider_name: impl AsRef<str>) -> Self {
-        let normalized = normalize_key(provider_name.as_ref());
-        Self {
-            key: format!("account_provider_{normalized}"),
+/// Current layout written by `AccountStore`:
+/// `{ "version": 1, "accounts": [AccountRecord, ...] }`.
+pub const ACCOUNT_STORE_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct StoredAccounts {
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    accounts: Vec<AccountRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tombstones: Vec<Tombstone>,
+    /// Id of the active account; always one of `accounts` when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    active: Option<String>,
+    /// Account ids, most recently activated or logged in first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    recently_used: Vec<String>,
+    /// Audit trail kept by `AccountStore::with_audit`, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    history: Vec<AuditEntry>,
+}
+
+impl StoredAccounts {
+    /// Inserts or replaces `account` and returns the record it replaced. A
+    /// local upsert is an explicit re-add, so it also drops any tombstone.
+    fn apply_upsert(
+        &mut self,
+        account: AccountRecord,
+    ) -> std::result::Result<Option<AccountRecord>, AccountError> {
+        if account.id.trim().is_empty() {
+            return Err(AccountError::InvalidRecord {
+                account_id: account.id,
+                reason: "account id is required".to_string(),
+            });
+        }
+        self.tombstones
+            .retain(|tombstone| tombstone.account_id != account.id);
+        match self
+            .accounts
+            .iter_mut()
+            .find(|existing| existing.id == account.id)
+        {
+            Some(existing) => {
+                ensure_unsealed_or_unsealing(existing, &account)?;
+                Ok(Some(std::mem::replace(existing, account)))
+            }
+            None => {
+                self.accounts.push(account);
+                Ok(None)
+            }
         }
     }
 
-    pub fn with_key(key: impl Into<String>) -> Self {
-        Self { key: key.into() }
+    /// `apply_upsert` that also moves `account` to the front, where the
+    /// single-account view reads "the" account from. Other records are kept.
+    fn save_primary(
+        &mut self,
+        account: AccountRecord,
+    ) -> std::result::Result<Option<AccountRecord>, AccountError> {
+        let account_id = account.id.clone();
+        let previous = self.apply_upsert(account)?;
+        if let Some(index) = self
+            .accounts
+            .iter()
+            .position(|account| account.id == account_id)
+        {
+            let account = self.accounts.remove(index);
+            self.accounts.insert(0, account);
+        }
+        Ok(previous)
     }
 
-    pub fn key(&self) -> &str {
-        &self.key
+    /// Adds `delta` to the integer at `extra[key]` of `account_id`, returning
+    /// the record as it was before and the new value.
+    fn increment_counter(
+        &mut self,
+        account_id: &str,
+        key: &str,
+        delta: i64,
+    ) -> std::result::Result<(AccountRecord, i64), AccountError> {
+        let account = self
+            .accounts
+            .iter_mut()
+            .find(|account| account.id == account_id)
+            .ok_or_else(|| AccountError::not_found(account_id))?;
+        if account.sealed {
+            return Err(AccountError::RecordSealed {
+                account_id: account.id.clone(),
+            });
+        }
+        let previous = account.clone();
+        let value = account
+            .extra_value(key)
+            .and_then(Value::as_i64)
+            .unwrap_or(0)
+            .saturating_add(delta);
+        account.set_extra_value(key, json!(value));
+        Ok((previous, value))
     }
 
-    pub async fn load(&self, app_handle: &AppHandle) -> Result<Option<AccountRecord>> {
-        local_storage_get_json(app_handle, self.key()).await
+    /// Drops the oldest audit entries of every account beyond `max_entries`
+    /// and returns how many were dropped.
+    fn trim_history(&mut self, max_entries: usize) -> usize {
+        let before = self.history.len();
+        let mut kept: HashMap<String, usize> = HashMap::new();
+        let mut newest_first = std::mem::take(&mut self.history);
+        newest_first.reverse();
+        newest_first.retain(|entry| {
+            let count = kept.entry(entry.account_id.clone()).or_default();
+            *count += 1;
+            *count <= max_entries
+        });
+        newest_first.reverse();
+        self.history = newest_first;
+        before - self.history.len()
     }
 
-    pub async fn save(&self, app_handle: &AppHandle, account: &AccountRecord) -> Result<()> {
-        local_storage_set_json(app_handle, self.key(), account).await
+    /// Appends `entry` and drops the oldest entries of that account beyond
+    /// `max_entries`.
+    fn record_audit(&mut self, entry: AuditEntry, max_entries: usize) {
+        let account_id = entry.account_id.clone();
+        self.history.push(entry);
+        let mut excess = self
+            .history
+            .iter()
+            .filter(|entry| entry.account_id == account_id)
+            .count()
+            .saturating_sub(max_entries);
+        self.history.retain(|entry| {
+            let drop = excess > 0 && entry.account_id == account_id;
+            excess -= usize::from(drop);
+            !drop
+        });
     }
 
-    pub async fn clear(&self, app_handle: &AppHandle) -> Result<()> {
-        local_storage_remove(app_handle, self.key()).await
+    /// First record from `make` whose id is not stored yet.
+    fn mint_unique(
+        &self,
+        make: impl Fn() -> AccountRecord,
+        max_tries: usize,
+    ) -> std::result::Result<AccountRecord, AccountError> {
+        let attempts = max_tries.max(1);
+        let mut last_id = String::new();
+        for _ in 0..attempts {
+            let account = make();
+            if !self.contains(&account.id) {
+                return Ok(account);
+            }
+            last_id = account.id;
+        }
+        Err(AccountError::IdCollision {
+            account_id: last_id,
+            attempts,
+        })
     }
 
-    pub async fn list_accounts(&self, app_handle: &AppHandle) -> Result<Vec<AccountRecord>> {
-        Ok(self.load(app_handle).await?.into_iter().collect())
+    /// Applies `defaults` if there are no accounts yet; see
+    /// `AccountStore::seed_if_empty`.
+    fn seed(&mut self, defaults: Vec<AccountRecord>) -> std::result::Result<bool, AccountError> {
+        if !self.accounts.is_empty() || defaults.is_empty() {
+            return Ok(false);
+        }
+        for account in defaults {
+            self.apply_upsert(account)?;
+        }
+        Ok(true)
     }
 
-    pub async fn get_account(
-        &self,
-        app_handle: &AppHandle,
+    fn contains(&self, account_id: &str) -> bool {
+        self.find(account_id).is_some()
+    }
+
+    fn find(&self, account_id: &str) -> Option<&AccountRecord> {
+        self.accounts
+            .iter()
+            .find(|account| account.id == account_id)
+    }
+
+    fn activate(&mut self, account_id: &str) {
+        self.active = Some(account_id.to_string());
+        self.mark_used(account_id);
+    }
+
+    fn mark_used(&mut self, account_id: &str) {
+        self.recently_used.retain(|id| id != account_id);
+        self.recently_used.insert(0, account_id.to_string());
+    }
+
+    /// Login bookkeeping for `record_login`: stamps the record and moves it to
+    /// the front of `recently_used` without activating it. Returns the record
+    /// as it was before, or `None` when `account_id` is not stored.
+    fn record_login(
+        &mut self,
         account_id: &str,
-    ) -> Result<Option<AccountRecord>> {
-        Ok(self
-            .load(app_handle)
-            .await?
-            .filter(|account| account.id == account_id))
+        now: u64,
+    ) -> std::result::Result<Option<AccountRecord>, AccountError> {
+        let Some(account) = self
+            .accounts
+            .iter_mut()
+            .find(|account| account.id == account_id)
+        else {
+            return Ok(None);
+        };
+        if account.sealed {
+            return Err(AccountError::RecordSealed {
+                account_id: account.id.clone(),
+            });
+        }
+        let previous = account.clone();
+        touch_login(account, now);
+        self.mark_used(account_id);
+        Ok(Some(previous))
     }
 
-    pub async fn upsert_account(
-        &self,
-        app_handle: &AppHandle,
-        account: AccountRecord,
-    ) -> Result<AccountRecord> {
-        if account.id.trim().is_empty() {
-            return Err(anyhow!("account id is required"));
+    /// Restores the active-account invariant after removals: forgets removed
+    /// ids and, if the active account was removed, re-points `active` as
+    /// `fallback` says, or clears it when no accounts remain. A store without
+    /// an active account keeps none.
+    fn repair_active(&mut self, fallback: ActiveFallback) {
+        let recently_used = std::mem::take(&mut self.recently_used);
+        self.recently_used = recently_used
+            .into_iter()
+            .filter(|id| self.contains(id))
+            .collect();
+        if self.active.as_deref().is_none_or(|id| self.contains(id)) {
+            return;
         }
-        self.save(app_handle, &account).await?;
-        Ok(account)
+        let first = || self.accounts.first().map(|account| account.id.clone());
+        self.active = match fallback {
+            ActiveFallback::MostRecentlyUsed => self.recently_used.first().cloned().or_else(first),
+            ActiveFallback::FirstInList => first(),
+            ActiveFallback::None => None,
+        };
+        if let Some(active) = self.active.clone() {
+            self.activate(&active);
+        }
+    }
+}
+
+/// Marker left behind by a removal so `AccountStore::reconcile` does not
+/// re-add the account from a remote copy. `deleted_at` is Unix milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tombstone {
+    pub account_id: String,
+    pub deleted_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchMode {
+    /// All records are written, or none.
+    Atomic,
+    /// Accepted records are written even if others are rejected.
+    Partial,
+}
+
+/// Outcome of a partial batch: accepted records as written and rejected
+/// records with the reason.
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    pub succeeded: Vec<AccountRecord>,
+    pub failed: Vec<(AccountRecord, AccountError)>,
+}
+
+impl BatchResult {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    Created,
+    Updated,
+    Removed,
+}
+
+/// One change recorded by an `AccountStore` built `with_audit`. `changes` is
+/// the field diff with token values redacted; it is empty for removals.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub account_id: String,
+    pub operation: AuditOperation,
+    /// Unix milliseconds.
+    pub at: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<FieldChange>,
+}
 
-    pub async fn remove_account(&self, app_handle: &AppHandle, account_id: &str) -> Result<()> {
-        if let Some(account) = self.load(app_handle).await? {
-            if account.id == account_id {
-                self.clear(app_handle).await?;
+impl AuditEntry {
+    /// Entry for an upsert of `account` over `previous`, or `None` when the
+    /// upsert changed nothing.
+    fn upserted(
+        previous: Option<&AccountRecord>,
+        account: &AccountRecord,
+        at: u64,
+    ) -> Option<Self> {
+        let (operation, changes) = match previous {
+            Some(previous) => (AuditOperation::Updated, previous.diff(account)),
+            None => {
+                let empty = AccountRecord {
+                    id: account.id.clone(),
+                    ..AccountRecord::default()
+                };
+                (AuditOperation::Created, empty.diff(account))
             }
+        };
+        if operation == AuditOperation::Updated && changes.is_empty() {
+            return None;
+        }
+        Some(Self {
+            account_id: account.id.clone(),
+            operation,
+            at,
+            changes: changes.redacted().changes,
+        })
+    }
+
+    fn removed(account_id: &str, at: u64) -> Self {
+        Self {
+            account_id: account_id.to_string(),
+            operation: AuditOperation::Removed,
+            at,
+            changes: Vec::new(),
         }
-        Ok(())
     }
 }
 
-fn normalize_key(input: &str) -> String {
-    input
-        .chars()
-        .map(|ch| {
-            if ch.is_ascii_alphanumeric() {
-                ch.to_ascii_lowercase()
-            } else {
-                '_'
-            }
+/// Dry run of `AccountStore::import_all`, as account ids. `contains_secrets`
+/// flags a backup carrying plaintext credentials (see
+/// `AccountRecord::secret_count`) so the UI can warn before importing it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPlan {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    /// Records that would be rejected, with the reason.
+    pub rejected: Vec<(String, String)>,
+    pub contains_secrets: bool,
+    pub secret_count: usize,
+}
+
+/// Outcome of `AccountStore::import_all`.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub batch: BatchResult,
+    pub contains_secrets: bool,
+    pub secret_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One compact JSON array.
+    #[default]
+    JsonArray,
+    /// One compact JSON record per line.
+    Ndjson,
+}
+
+/// How secrets appear in an export.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportSecrets {
+    /// As `AccountRecord::redacted`.
+    #[default]
+    Redacted,
+    /// Encrypted with the store's cipher; fails without one.
+    Encrypted,
+    Plaintext,
+}
+
+/// What `AccountStore::remove_account` removed, and the active account id
+/// after the removal.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RemovedAccount {
+    pub account: AccountRecord,
+    pub active: Option<String>,
+}
+
+/// Outcome of `AccountStore::reconcile`, as account ids.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub skipped_tombstoned: Vec<String>,
+    pub skipped_sealed: Vec<String>,
+}
+
+/// Which layout a stored value was written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreLayout {
+    /// Nothing stored (`null`).
+    Empty,
+    /// `{ "version": .., "accounts": [..] }`.
+    Envelope,
+    /// A bare array of records.
+    Array,
+    /// The single-record object written before multi-account storage.
+    LegacySingle,
+}
+
+/// Parsed contents of an account store, whatever layout it was read from.
+#[derive(Debug, Clone)]
+pub struct LoadedStore {
+    layout: StoreLayout,
+    stored: StoredAccounts,
+}
+
+impl LoadedStore {
+    pub fn layout(&self) -> StoreLayout {
+        self.layout
+    }
+
+    /// Schema version as stored; 0 for the pre-envelope layouts.
+    pub fn version(&self) -> u32 {
+        self.stored.version
+    }
+
+    pub fn accounts(&self) -> &[AccountRecord] {
+        &self.stored.accounts
+    }
+
+    pub fn active_account_id(&self) -> Option<&str> {
+        self.stored.active.as_deref()
+    }
+
+    pub fn tombstones(&self) -> &[Tombstone] {
+        &self.stored.tombstones
+    }
+
+    pub fn into_accounts(self) -> Vec<AccountRecord> {
+        self.stored.accounts
+    }
+}
+
+/// The one parse entry point for stored account data. Accepts the current
+/// envelope, a bare array of records, and the legacy single-record object;
+/// anything else, including arbitrary or corrupted JSON, is an error and
+/// never a panic. Secrets are returned as stored (still encrypted).
+pub fn from_stored_value(value: Value) -> Result<LoadedStore> {
+    let (layout, stored) = match value {
+        Value::Null => (StoreLayout::Empty, StoredAccounts::default()),
+        Value::Array(_) => (
+            StoreLayout::Array,
+            StoredAccounts {
+                version: 0,
+                accounts: serde_json::from_value(value)?,
+                ..StoredAccounts::default()
+            },
+        ),
+        Value::Object(ref object) if object.contains_key("accounts") => {
+            (StoreLayout::Envelope, serde_json::from_value(value)?)
+        }
+        Value::Object(_) => (
+            StoreLayout::LegacySingle,
+            StoredAccounts {
+                version: 0,
+                accounts: vec![serde_json::from_value(value)?],
+                ..StoredAccounts::default()
+            },
+        ),
+        other => return Err(anyhow!("unexpected account store payload: {other}")),
+    };
+    Ok(LoadedStore { layout, stored })
+}
+
+/// How a store's schema version relates to `ACCOUNT_STORE_SCHEMA_VERSION`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum CompatibilityStatus {
+    /// Current version, or nothing stored.
+    Compatible,
+    /// Older version; upgraded on the next write or `compact`.
+    NeedsMigration { version: u32 },
+    /// Written by a newer crate. Writing to it would drop whatever this
+    /// version does not understand.
+    TooNew { version: u32 },
+    /// The store could not be read, or its layout or version is corrupt.
+    Unreadable { error: String },
+}
+
+impl CompatibilityStatus {
+    /// Whether the store can be used as-is or after migration.
+    pub fn is_usable(&self) -> bool {
+        !matches!(self, Self::TooNew { .. } | Self::Unreadable { .. })
+    }
+
+    /// Folds a failed check into `Unreadable`.
+    fn from_check(check: Result<Self>) -> Self {
+        check.unwrap_or_else(|err| Self::Unreadable {
+            error: format!("{err:#}"),
         })
-        .collect()
+    }
+}
+
+/// Compatibility of a stored value, judged from its layout and `version`
+/// alone so that stores from newer versions are not parsed as if current.
+fn schema_compatibility(value: &Value) -> Result<CompatibilityStatus> {
+    let version = match value {
+        Value::Null => return Ok(CompatibilityStatus::Compatible),
+        Value::Object(object) if object.contains_key("accounts") => match object.get("version") {
+            None | Some(Value::Null) => 0,
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or_else(|| anyhow!("unexpected schema version: {version}"))?,
+        },
+        Value::Array(_) | Value::Object(_) => 0,
+        other => return Err(anyhow!("unexpected account store payload: {other}")),
+    };
+    Ok(match version.cmp(&ACCOUNT_STORE_SCHEMA_VERSION) {
+        std::cmp::Ordering::Less => CompatibilityStatus::NeedsMigration { version },
+        std::cmp::Ordering::Equal => CompatibilityStatus::Compatible,
+        std::cmp::Ordering::Greater => CompatibilityStatus::TooNew { version },
+    })
+}
+
+/// Raw record values of any supported store layout.
+fn stored_account_values(value: Value) -> Result<Vec<Value>> {
+    match value {
+        Value::Null => Ok(Vec::new()),
+        Value::Array(values) => Ok(values),
+        Value::Object(mut object) if object.contains_key("accounts") => {
+            match object.remove("accounts") {
+                Some(Value::Array(values)) => Ok(values),
+                Some(Value::Null) | None => Ok(Vec::new()),
+                Some(other) => Err(anyhow!("unexpected accounts payload: {other}")),
+            }
+        }
+        value @ Value::Object(_) => Ok(vec![value]),
+        other => Err(anyhow!("unexpected account store payload: {other}")),
+    }
+}
+
+/// Outcome of `AccountStore::compact`. Sizes are the compact JSON encoding of
+/// the stored value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactReport {
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+    pub duplicates_dropped: usize,
+    pub invalid_dropped: usize,
+    pub tombstones_pruned: usize,
+    pub history_pruned: usize,
+    pub upgraded: bool,
+}
+
+impl CompactReport {
+    pub fn bytes_reclaimed(&self) -> usize {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// Encrypts account secrets before they reach localStorage.
+pub trait TokenCipher: Send + Sync {
+    fn encrypt(&self, plain: &str) -> Result<String>;
+
+    fn decrypt(&self, stored: &str) -> Result<String>;
+
+    /// Recognizes values produced by `encrypt`, so plaintext written before
+    /// encryption was enabled can still be read and migrated.
+    fn is_encrypted(&self, stored: &str) -> bool;
+}
+
+/// Where an `AccountStore` keeps its value. `FrontendStorage`, the default,
+/// is the host's localStorage; other backends may ignore `app_handle`.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    async fn get(&self, app_handle: &AppHandle, key: &str) -> Result<Option<Value>>;
+
+    async fn set(&self, app_handle: &AppHandle, key: &str, value: &Value) -> Result<()>;
+
+    async fn remove(&self, app_handle: &AppHandle, key: &str) -> Result<()>;
+
+    /// Stored keys that start with `prefix` (`""` lists all).
+    async fn keys(&self, app_handle: &AppHandle, prefix: &str) -> Result<Vec<String>>;
+}
+
+/// The host's localStorage, reached through the frontend bridge.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrontendStorage;
+
+#[async_trait]
+impl StorageBackend for FrontendStorage {
+    async fn get(&self, app_handle: &AppHandle, key: &str) -> Result<Option<Value>> {
+        local_storage_get_json(app_handle, key).await
+    }
+
+    async fn set(&self, app_handle: &AppHandle, key: &str, value: &Value) -> Result<()> {
+        local_storage_set_json(app_handle, key, value).await
+    }
+
+    async fn remove(&self, app_handle: &AppHandle, key: &str) -> Result<()> {
+        local_storage_remove(app_handle, key).await
+    }
+
+    async fn keys(&self, app_handle: &AppHandle, prefix: &str) -> Result<Vec<String>> {
+        local_storage_keys(app_handle, prefix).await
+    }
+}
+
+/// What a write leaving no accounts persists; see `AccountStore::load_accounts`
+/// for how each state reads back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyPolicy {
+    /// Keep the key with an empty account list: "initialized, no accounts".
+    #[default]
+    StoreEmpty,
+    /// Remove the key, as `clear` does: back to "never initialized". The key
+    /// is kept while tombstones or audit history still need storing.
+    RemoveKey,
+}
+
+/// Where `active` moves when the active account is removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ActiveFallback {
+    /// The most recently used remaining account, else the first one.
+    #[default]
+    MostRecentlyUsed,
+    /// The first remaining account in store order.
+    FirstInList,
+    /// No account becomes active.
+    None,
+}
+
+/// Multi-account store persisted under a single localStorage key.
+#[derive(Clone)]
+pub struct AccountStore {
+    key: String,
+    backend: Arc<dyn StorageBackend>,
+    /// `MAINTENANCE_MODE` outside of tests.
+    maintenance: &'static AtomicBool,
+    cipher: Option<Arc<dyn TokenCipher>>,
+    tombstone_ttl: Option<Duration>,
+    active_fallback: ActiveFallback,
+    audit_max_entries: Option<usize>,
+    empty_policy: EmptyPolicy,
+}
+
+impl std::fmt::Debug for AccountStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccountStore")
+            .field("key", &self.key)
+            .field("encrypted", &self.cipher.is_some())
+            .field("tombstone_ttl", &self.tombstone_ttl)
+            .field("active_fallback", &self.active_fallback)
+            .field("audit_max_entries", &self.audit_max_entries)
+            .field("empty_policy", &self.empty_policy)
+            .finish()
+    }
+}
+
+impl AccountStore {
+    pub fn new(provider_name: impl AsRef<str>) -> Self {
+        let normalized = normalize_key(provider_name.as_ref());
+        Self::with_key(format!("{ACCOUNT_STORE_KEY_PREFIX}{normalized}"))
+    }
+
+    /// Store of `provider_name` accounts belonging to `user_id`, isolated
+    /// from other users and from the global store of `new`. Both parts are
+    /// normalized like `new` does.
+    pub fn for_user(user_id: impl AsRef<str>, provider_name: impl AsRef<str>) -> Self {
+        let user = normalize_key(user_id.as_ref());
+        let provider = normalize_key(provider_name.as_ref());
+        Self::with_key(format!(
+            "{USER_STORE_KEY_PREFIX}{user}_{ACCOUNT_STORE_KEY_PREFIX}{provider}"
+        ))
+    }
+
+    /// `for_user` with the current `user_scope`, or `new` when unscoped.
+    pub fn for_current_user(provider_name: impl AsRef<str>) -> Self {
+        match crate::user_scope() {
+            Some(user_id) => Self::for_user(user_id, provider_name),
+            None => Self::new(provider_name),
+        }
+    }
+
+    pub fn with_key(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            backend: Arc::new(FrontendStorage),
+            maintenance: &MAINTENANCE_MODE,
+            cipher: None,
+            tombstone_ttl: None,
+            active_fallback: ActiveFallback::default(),
+            audit_max_entries: None,
+            empty_policy: EmptyPolicy::default(),
+        }
+    }
+
+    /// Records a tombstone for every removed account and keeps it for `ttl`,
+    /// during which `reconcile` refuses to re-add that id. Without this,
+    /// removals leave no trace and tombstones already stored are ignored.
+    pub fn with_tombstones(mut self, ttl: Duration) -> Self {
+        self.tombstone_ttl = Some(ttl);
+        self
+    }
+
+    /// How `active` is re-pointed when the active account is removed;
+    /// `ActiveFallback::MostRecentlyUsed` by default.
+    pub fn with_active_fallback(mut self, strategy: ActiveFallback) -> Self {
+        self.active_fallback = strategy;
+        self
+    }
+
+    /// Keeps an audit trail in the store: every write that creates, changes
+    /// or removes a record (upserts, counters, logins, seals, reconciles and
+    /// the single-account view included) appends an `AuditEntry`, keeping
+    /// the newest `max_entries` per account (removed accounts included).
+    /// Upserts that change nothing are not recorded. Off by default since it
+    /// grows the stored blob.
+    pub fn with_audit(mut self, max_entries: usize) -> Self {
+        self.audit_max_entries = Some(max_entries);
+        self
+    }
+
+    /// Whether removing the last account keeps an empty list or removes the
+    /// key; `EmptyPolicy::StoreEmpty` by default.
+    pub fn with_empty_policy(mut self, policy: EmptyPolicy) -> Self {
+        self.empty_policy = policy;
+        self
+    }
+
+    /// Keeps the store in `backend` instead of the host's localStorage. Reads
+    /// are coalesced and writes serialized per key, so every store sharing a
+    /// key must use the same backend.
+    pub fn with_backend(mut self, backend: Arc<dyn StorageBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Follows `flag` instead of the process-wide `set_maintenance_mode`, so
+    /// tests can switch maintenance mode without affecting each other.
+    #[cfg(test)]
+    fn with_maintenance_flag(mut self, flag: &'static AtomicBool) -> Self {
+        self.maintenance = flag;
+        self
+    }
+
+    /// Encrypts tokens on every write and decrypts them on read. Plaintext
+    /// tokens already in storage stay readable; see `encrypt_existing_tokens`.
+    pub fn with_token_cipher(mut self, cipher: Arc<dyn TokenCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// View of this store with the old "one account per provider" semantics.
+    pub fn single(&self) -> SingleAccountStore {
+        SingleAccountStore {
+            store: self.clone(),
+        }
+    }
+
+    /// Serializes read-modify-write sequences on this store's key across every
+    /// `AccountStore` instance in the process.
+    async fn lock(&self) -> OwnedMutexGuard<()> {
+        let lock = {
+            let locks = STORE_LOCKS.get_or_init(|| Mutex::new(HashMap::new()));
+            let mut locked = locks.lock().unwrap();
+            Arc::clone(locked.entry(self.key.clone()).or_default())
+        };
+        lock.lock_owned().await
+    }
+
+    /// `lock` for regular writes, which are refused in maintenance mode.
+    async fn lock_for_write(&self) -> Result<OwnedMutexGuard<()>> {
+        let guard = self.lock().await;
+        if self.maintenance.load(Ordering::SeqCst) {
+            return Err(AccountError::MaintenanceMode {
+                store: self.key.clone(),
+            }
+            .into());
+        }
+        Ok(guard)
+    }
+
+    /// Reads the store key from the backend. Concurrent calls for the same
+    /// key share one in-flight read and each get a clone of its result; a
+    /// call made after a write always issues a fresh read.
+    async fn fetch_stored_value(&self, app_handle: &AppHandle) -> Result<Option<Value>> {
+        let load = load_flights()
+            .entry(self.key.clone())
+            .or_default()
+            .join_or_start(|| {
+                let app_handle = app_handle.clone();
+                let key = self.key.clone();
+                let backend = Arc::clone(&self.backend);
+                async move { backend.get(&app_handle, &key).await.map_err(Arc::new) }
+                    .boxed()
+                    .shared()
+            });
+        let result = load.clone().await;
+        if let Some(flight) = load_flights().get_mut(&self.key) {
+            flight.settle(&load);
+        }
+        result.map_err(|err| clone_shared_error(&err))
+    }
+
+    /// Ends read coalescing for values fetched before the write that just
+    /// happened.
+    fn invalidate_loads(&self) {
+        load_flights()
+            .entry(self.key.clone())
+            .or_default()
+            .invalidate();
+    }
+
+    /// Pre-flight check of the stored schema version; see
+    /// `check_storage_compatibility`.
+    pub async fn compatibility(&self, app_handle: &AppHandle) -> Result<CompatibilityStatus> {
+        let value = self.fetch_stored_value(app_handle).await?;
+        schema_compatibility(&value.unwrap_or_default())
+            .with_context(|| format!("check account store {}", self.key))
+    }
+
+    /// Reads the store as persisted, without decrypting secrets.
+    async fn read_raw(&self, app_handle: &AppHandle) -> Result<StoredAccounts> {
+        let value = self.fetch_stored_value(app_handle).await?;
+        value
+            .map(|value| from_stored_value(value).map(|loaded| loaded.stored))
+            .transpose()
+            .with_context(|| format!("parse account store {}", self.key))
+            .map(Option::unwrap_or_default)
+    }
+
+    async fn read(&self, app_handle: &AppHandle) -> Result<StoredAccounts> {
+        let mut stored = self.read_raw(app_handle).await?;
+        self.decrypt_accounts(&mut stored.accounts)?;
+        Ok(stored)
+    }
+
+    /// Persists `stored`, upgrading it to the current schema version and
+    /// encrypting secrets when a cipher is configured, or removes the key when
+    /// `EmptyPolicy::RemoveKey` applies. `stored` itself keeps the plaintext
+    /// values.
+    async fn write(&self, app_handle: &AppHandle, stored: &mut StoredAccounts) -> Result<()> {
+        stored.version = ACCOUNT_STORE_SCHEMA_VERSION;
+        let result = match self.cipher.as_deref() {
+            _ if self.writes_as_removal(stored) => {
+                self.backend.remove(app_handle, self.key()).await
+            }
+            None => self.persist(app_handle, stored).await,
+            Some(cipher) => {
+                let mut encrypted = stored.clone();
+                for account in &mut encrypted.accounts {
+                    encrypt_account_secrets(cipher, account)?;
+                }
+                self.persist(app_handle, &encrypted).await
+            }
+        };
+        // A failed write may still have reached storage.
+        self.invalidate_loads();
+        result
+    }
+
+    async fn persist(&self, app_handle: &AppHandle, stored: &StoredAccounts) -> Result<()> {
+        let value = serde_json::to_value(stored)
+            .with_context(|| format!("serialize account store {}", self.key))?;
+        self.backend.set(app_handle, self.key(), &value).await
+    }
+
+    fn writes_as_removal(&self, stored: &StoredAccounts) -> bool {
+        self.empty_policy == EmptyPolicy::RemoveKey
+            && stored.accounts.is_empty()
+            && stored.tombstones.is_empty()
+            && stored.history.is_empty()
+    }
+
+    fn decrypt_accounts(&self, accounts: &mut [AccountRecord]) -> Result<()> {
+        let Some(cipher) = self.cipher.as_deref() else {
+            return Ok(());
+        };
+        for account in accounts {
+            decrypt_account_secrets(cipher, account)
+                .with_context(|| format!("decrypt secrets of account {}", account.id))?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the store in its canonical form: the current envelope layout,
+    /// no records without an id, and one record per id (the last one stored
+    /// wins, at the position of the first). Bookkeeping is pruned too:
+    /// tombstones older than the `with_tombstones` ttl, and audit entries
+    /// beyond the `with_audit` cap; either is left alone when its option is
+    /// off. Nothing is written when the store is already canonical.
+    pub async fn compact(&self, app_handle: &AppHandle) -> Result<CompactReport> {
+        let _guard = self.lock().await;
+        let Some(value) = self.fetch_stored_value(app_handle).await? else {
+            return Ok(CompactReport::default());
+        };
+        let bytes_before = serde_json::to_vec(&value)?.len();
+        let mut stored = from_stored_value(value)
+            .map(|loaded| loaded.stored)
+            .with_context(|| format!("parse account store {}", self.key))?;
+        let upgraded = stored.version != ACCOUNT_STORE_SCHEMA_VERSION;
+
+        let (invalid_dropped, duplicates_dropped) = canonicalize_accounts(&mut stored.accounts);
+        let tombstones_pruned = match self.tombstone_ttl {
+            Some(ttl) => {
+                let before = stored.tombstones.len();
+                prune_tombstones(&mut stored.tombstones, ttl, now_millis());
+                before - stored.tombstones.len()
+            }
+            None => 0,
+        };
+        let history_pruned = self
+            .audit_max_entries
+            .map_or(0, |max_entries| stored.trim_history(max_entries));
+        stored.version = ACCOUNT_STORE_SCHEMA_VERSION;
+        let bytes_after = serde_json::to_vec(&stored)?.len();
+
+        let changed = invalid_dropped + duplicates_dropped + tombstones_pruned + history_pruned;
+        if upgraded || changed > 0 || bytes_after != bytes_before {
+            let written = self.persist(app_handle, &stored).await;
+            self.invalidate_loads();
+            written?;
+        }
+        Ok(CompactReport {
+            bytes_before,
+            bytes_after,
+            duplicates_dropped,
+            invalid_dropped,
+            tombstones_pruned,
+            history_pruned,
+            upgraded,
+        })
+    }
+
+    /// Re-saves the store through the configured cipher so tokens written
+    /// before encryption was enabled get encrypted too. Returns how many
+    /// plaintext tokens were found; running it again returns 0.
+    pub async fn encrypt_existing_tokens(&self, app_handle: &AppHandle) -> Result<usize> {
+        let cipher = self
+            .cipher
+            .as_deref()
+            .ok_or_else(|| anyhow!("account store {} has no token cipher", self.key))?;
+        let _guard = self.lock().await;
+        let mut stored = self.read_raw(app_handle).await?;
+        let plaintext = stored
+            .accounts
+            .iter()
+            .map(|account| plaintext_secret_count(cipher, account))
+            .sum();
+        if plaintext > 0 {
+            self.decrypt_accounts(&mut stored.accounts)?;
+            self.write(app_handle, &mut stored).await?;
+        }
+        Ok(plaintext)
+    }
+
+    #[deprecated(note = "use `list_accounts`, or `single().load()` for the old semantics")]
+    pub async fn load(&self, app_handle: &AppHandle) -> Result<Option<AccountRecord>> {
+        self.single().load(app_handle).await
+    }
+
+    #[deprecated(note = "use `upsert_account`, or `single().save()` for the old semantics")]
+    pub async fn save(&self, app_handle: &AppHandle, account: &AccountRecord) -> Result<()> {
+        self.single().save(app_handle, account).await
+    }
+
+    /// Removes every account and the storage key itself.
+    pub async fn clear(&self, app_handle: &AppHandle) -> Result<()> {
+        let _guard = self.lock_for_write().await?;
+        let removed = self.backend.remove(app_handle, self.key()).await;
+        self.invalidate_loads();
+        removed?;
+        emit_account_event(self.key(), AccountChange::Cleared);
+        Ok(())
+    }
+
+    pub async fn list_accounts(&self, app_handle: &AppHandle) -> Result<Vec<AccountRecord>> {
+        Ok(self.read(app_handle).await?.accounts)
+    }
+
+    /// Records matching `query`, filtered in memory after one load.
+    pub async fn list_accounts_query(
+        &self,
+        app_handle: &AppHandle,
+        query: &AccountQuery,
+    ) -> Result<Vec<AccountRecord>> {
+        Ok(query.apply(self.list_accounts(app_handle).await?))
+    }
+
+    /// Like `list_accounts`, but tells "never initialized" (`None`: the key
+    /// is absent) from "no accounts" (`Some(vec![])`). The key is absent
+    /// before the first write and after `clear`; removing the last account
+    /// leaves `Some(vec![])` under `EmptyPolicy::StoreEmpty` and `None` under
+    /// `EmptyPolicy::RemoveKey`.
+    pub async fn load_accounts(
+        &self,
+        app_handle: &AppHandle,
+    ) -> Result<Option<Vec<AccountRecord>>> {
+        let Some(value) = self.fetch_stored_value(app_handle).await? else {
+            return Ok(None);
+        };
+        let mut accounts = from_stored_value(value)
+            .with_context(|| format!("parse account store {}", self.key))?
+            .stored
+            .accounts;
+        self.decrypt_accounts(&mut accounts)?;
+        Ok(Some(accounts))
+    }
+
+    /// Records bucketed by `extra[key]`; see `models::group_by_extra` for how
+    /// values map to buckets. Records without a usable value land in the `""`
+    /// bucket; `group_by_extra_or` picks another name for it.
+    pub async fn group_by_extra(
+        &self,
+        app_handle: &AppHandle,
+        key: &str,
+    ) -> Result<BTreeMap<String, Vec<AccountRecord>>> {
+        self.group_by_extra_or(app_handle, key, "").await
+    }
+
+    pub async fn group_by_extra_or(
+        &self,
+        app_handle: &AppHandle,
+        key: &str,
+        default_bucket: &str,
+    ) -> Result<BTreeMap<String, Vec<AccountRecord>>> {
+        let accounts = self.list_accounts(app_handle).await?;
+        Ok(group_by_extra(accounts, key, default_bucket))
+    }
+
+    /// Point-in-time copy of every record, read while holding the store lock
+    /// so no mutation through `AccountStore` is half applied. It is a
+    /// snapshot, not a live view: later changes are not reflected in it.
+    pub async fn iter_snapshot(&self, app_handle: &AppHandle) -> Result<Vec<AccountRecord>> {
+        let _guard = self.lock().await;
+        self.list_accounts(app_handle).await
+    }
+
+    /// Like `list_accounts`, but salvages records with malformed optional
+    /// fields by resetting those fields (see
+    /// `AccountRecord::from_value_tolerant`). Records that cannot be salvaged
+    /// are skipped. Every reset or skip is logged.
+    pub async fn load_tolerant(&self, app_handle: &AppHandle) -> Result<Vec<AccountRecord>> {
+        let value = self.fetch_stored_value(app_handle).await?;
+        let values = stored_account_values(value.unwrap_or_default())
+            .with_context(|| format!("parse account store {}", self.key))?;
+        let mut accounts = Vec::with_capacity(values.len());
+        for value in values {
+            match AccountRecord::from_value_tolerant(value) {
+                Ok((mut account, reset)) => {
+                    self.decrypt_accounts(std::slice::from_mut(&mut account))?;
+                    if !reset.is_empty() {
+                        log::warn!(
+                            "[AccountStore] {} account {} had malformed fields reset: {}",
+                            self.key,
+                            account.id,
+                            reset.join(", ")
+                        );
+                    }
+                    accounts.push(account);
+                }
+                Err(err) => {
+                    log::warn!(
+                        "[AccountStore] {} skipped unrecoverable account: {}",
+                        self.key,
+                        err
+                    );
+                }
+            }
+        }
+        Ok(accounts)
+    }
+
+    /// Returns `None` when `account_id` is not stored.
+    pub async fn get_account(
+        &self,
+        app_handle: &AppHandle,
+        account_id: &str,
+    ) -> Result<Option<AccountRecord>> {
+        Ok(self
+            .list_accounts(app_handle)
+            .await?
+            .into_iter()
+            .find(|account| account.id == account_id))
+    }
+
+    /// Runs `f` on the record with `account_id` in place, without cloning it,
+    /// while the store lock is held, and returns its result, or `None` when
+    /// there is no such record. The borrow only lives for the call: `R`
+    /// cannot borrow from the record, so `f` copies out what it needs. `f`
+    /// must not call back into this store, which would deadlock on the lock.
+    /// Use `get_account` to keep the record itself.
+    pub async fn with_account<R>(
+        &self,
+        app_handle: &AppHandle,
+        account_id: &str,
+        f: impl FnOnce(&AccountRecord) -> R,
+    ) -> Result<Option<R>> {
+        let _guard = self.lock().await;
+        let stored = self.read(app_handle).await?;
+        Ok(stored.find(account_id).map(f))
+    }
+
+    /// Inserts `account`, or replaces the stored record with the same id in
+    /// place. Fails with `AccountError::RecordSealed` when the stored record
+    /// is sealed and `account` would change anything besides unsealing it.
+    pub async fn upsert_account(
+        &self,
+        app_handle: &AppHandle,
+        account: AccountRecord,
+    ) -> Result<AccountRecord> {
+        if account.id.trim().is_empty() {
+            return Err(AccountError::InvalidRecord {
+                account_id: account.id,
+                reason: "account id is required".to_string(),
+            }
+            .into());
+        }
+        let _guard = self.lock_for_write().await?;
+        let mut stored = self.read(app_handle).await?;
+        let previous = stored.apply_upsert(account.clone())?;
+        self.audit_upsert(&mut stored, previous.as_ref(), &account);
+        self.write(app_handle, &mut stored).await?;
+        emit_account_event(
+            self.key(),
+            AccountChange::upserted(previous.as_ref(), account.clone()),
+        );
+        Ok(account)
+    }
+
+    /// Stores a record from `make` only if its id is not taken, calling `make`
+    /// again (it should mint a fresh id each time) up to `max_tries` times in
+    /// total, at least once; `DEFAULT_ID_COLLISION_TRIES` suits random ids.
+    /// Fails with `AccountError::IdCollision` when every id was taken. The
+    /// check and the write happen under the store lock.
+    pub async fn create_account_unique(
+        &self,
+        app_handle: &AppHandle,
+        make: impl Fn() -> AccountRecord,
+        max_tries: usize,
+    ) -> Result<AccountRecord> {
+        let _guard = self.lock_for_write().await?;
+        let mut stored = self.read(app_handle).await?;
+        let account = stored.mint_unique(make, max_tries)?;
+        stored.apply_upsert(account.clone())?;
+        self.audit_upsert(&mut stored, None, &account);
+        self.write(app_handle, &mut stored).await?;
+        emit_account_event(self.key(), AccountChange::upserted(None, account.clone()));
+        Ok(account)
+    }
+
+    /// First-run provisioning: stores `defaults` only when the store has no
+    /// accounts, returning whether it did. The emptiness check and the write
+    /// happen under the store lock, so concurrent callers seed at most once.
+    /// Seed records are validated like any upsert; if one is rejected nothing
+    /// is written.
+    pub async fn seed_if_empty(
+        &self,
+        app_handle: &AppHandle,
+        defaults: Vec<AccountRecord>,
+    ) -> Result<bool> {
+        let _guard = self.lock_for_write().await?;
+        let mut stored = self.read(app_handle).await?;
+        if !stored.seed(defaults.clone())? {
+            return Ok(false);
+        }
+        for account in &defaults {
+            self.audit_upsert(&mut stored, None, account);
+        }
+        self.write(app_handle, &mut stored).await?;
+        for account in defaults {
+            emit_account_event(self.key(), AccountChange::upserted(None, account));
+        }
+        Ok(true)
+    }
+
+    /// Upserts many records in one write. `BatchMode::Atomic` writes nothing
+    /// and fails with the first rejection if any record is rejected;
+    /// `BatchMode::Partial` writes the accepted records and reports each
+    /// rejected one with its reason. Records are applied in order, so a later
+    /// record with the same id wins.
+    pub async fn upsert_accounts(
+        &self,
+        app_handle: &AppHandle,
+        accounts: Vec<AccountRecord>,
+        mode: BatchMode,
+    ) -> Result<BatchResult> {
+        let _guard = self.lock_for_write().await?;
+        let mut stored = self.read(app_handle).await?;
+        let total = accounts.len();
+        let mut result = BatchResult::default();
+        let mut previous = Vec::new();
+        for account in accounts {
+            match stored.apply_upsert(account.clone()) {
+                Ok(replaced) => {
+                    previous.push(replaced);
+                    result.succeeded.push(account);
+                }
+                Err(err) => result.failed.push((account, err)),
+            }
+        }
+        if mode == BatchMode::Atomic && !result.failed.is_empty() {
+            let (_, err) = result.failed.swap_remove(0);
+            return Err(anyhow::Error::new(err).context(format!(
+                "atomic batch of {total} records rejected; nothing was written"
+            )));
+        }
+        if !result.succeeded.is_empty() {
+            for (account, previous) in result.succeeded.iter().zip(&previous) {
+                self.audit_upsert(&mut stored, previous.as_ref(), account);
+            }
+            self.write(app_handle, &mut stored).await?;
+            for (account, previous) in result.succeeded.iter().zip(previous) {
+                emit_account_event(
+                    self.key(),
+                    AccountChange::upserted(previous.as_ref(), account.clone()),
+                );
+            }
+        }
+        Ok(result)
+    }
+
+    /// Writes every record to `writer` one at a time. The bridge hands over
+    /// the stored value in one piece, but records are parsed, decrypted and
+    /// serialized one by one from it, so neither the parsed account list nor
+    /// a serialized copy of the export is ever held in full. Returns how many
+    /// records were written.
+    pub async fn export_all_to_writer<W: std::io::Write>(
+        &self,
+        app_handle: &AppHandle,
+        writer: &mut W,
+        format: ExportFormat,
+        secrets: ExportSecrets,
+    ) -> Result<usize> {
+        let value = self.fetch_stored_value(app_handle).await?;
+        let values = stored_account_values(value.unwrap_or_default())
+            .with_context(|| format!("parse account store {}", self.key))?;
+        write_records(writer, self.exported_records(values, secrets)?, format)
+    }
+
+    /// Turns stored record values into records as `secrets` says, lazily.
+    fn exported_records(
+        &self,
+        values: Vec<Value>,
+        secrets: ExportSecrets,
+    ) -> Result<impl Iterator<Item = Result<AccountRecord>> + '_> {
+        let cipher = match secrets {
+            ExportSecrets::Encrypted => Some(
+                self.cipher
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("account store {} has no token cipher", self.key))?,
+            ),
+            ExportSecrets::Redacted | ExportSecrets::Plaintext => None,
+        };
+        Ok(values.into_iter().map(move |value| {
+            let mut account: AccountRecord = serde_json::from_value(value)
+                .with_context(|| format!("parse account store {}", self.key))?;
+            self.decrypt_accounts(std::slice::from_mut(&mut account))?;
+            match (secrets, cipher) {
+                (ExportSecrets::Redacted, _) => account = account.redacted(),
+                (ExportSecrets::Encrypted, Some(cipher)) => {
+                    encrypt_account_secrets(cipher, &mut account)?
+                }
+                _ => {}
+            }
+            Ok(account)
+        }))
+    }
+
+    /// Classifies `accounts` against the current records without writing
+    /// anything, and scans them for plaintext credentials.
+    pub async fn import_plan(
+        &self,
+        app_handle: &AppHandle,
+        accounts: &[AccountRecord],
+    ) -> Result<ImportPlan> {
+        let stored = self.read(app_handle).await?;
+        Ok(plan_import(stored, accounts))
+    }
+
+    /// Imports `accounts` as `upsert_accounts` does with `mode`, reporting
+    /// the same credential scan as `import_plan`.
+    pub async fn import_all(
+        &self,
+        app_handle: &AppHandle,
+        accounts: Vec<AccountRecord>,
+        mode: BatchMode,
+    ) -> Result<ImportReport> {
+        let secret_count = accounts.iter().map(AccountRecord::secret_count).sum();
+        let batch = self.upsert_accounts(app_handle, accounts, mode).await?;
+        Ok(ImportReport {
+            batch,
+            contains_secrets: secret_count > 0,
+            secret_count,
+        })
+    }
+
+    /// Removes the record and returns it along with the active account id
+    /// afterwards. When the active account itself is removed, `active` moves
+    /// per `with_active_fallback` in the same write. Returns
+    /// `None` without writing when `account_id` is not stored.
+    pub async fn remove_account(
+        &self,
+        app_handle: &AppHandle,
+        account_id: &str,
+    ) -> Result<Option<RemovedAccount>> {
+        let (removed, active) = self
+            .remove_where(app_handle, |account| account.id == account_id)
+            .await?;
+        Ok(removed
+            .into_iter()
+            .next()
+            .map(|account| RemovedAccount { account, active }))
+    }
+
+    /// Removes every record matching `predicate` and returns the removed
+    /// records. The active account is re-pointed as in `remove_account`.
+    pub async fn remove_accounts_where(
+        &self,
+        app_handle: &AppHandle,
+        predicate: impl Fn(&AccountRecord) -> bool,
+    ) -> Result<Vec<AccountRecord>> {
+        let (removed, _) = self.remove_where(app_handle, predicate).await?;
+        Ok(removed)
+    }
+
+    async fn remove_where(
+        &self,
+        app_handle: &AppHandle,
+        predicate: impl Fn(&AccountRecord) -> bool,
+    ) -> Result<(Vec<AccountRecord>, Option<String>)> {
+        let _guard = self.lock_for_write().await?;
+        let mut stored = self.read(app_handle).await?;
+        let (removed, kept): (Vec<_>, Vec<_>) = stored
+            .accounts
+            .into_iter()
+            .partition(|account| predicate(account));
+        stored.accounts = kept;
+        if !removed.is_empty() {
+            if let Some(ttl) = self.tombstone_ttl {
+                let now = now_millis();
+                prune_tombstones(&mut stored.tombstones, ttl, now);
+                record_tombstones(
+                    &mut stored.tombstones,
+                    removed.iter().map(|account| account.id.as_str()),
+                    now,
+                );
+            }
+            stored.repair_active(self.active_fallback);
+            if let Some(max_entries) = self.audit_max_entries {
+                let now = now_millis();
+                for account in &removed {
+                    stored.record_audit(AuditEntry::removed(&account.id, now), max_entries);
+                }
+            }
+            self.write(app_handle, &mut stored).await?;
+            for account in &removed {
+                emit_account_event(
+                    self.key(),
+                    AccountChange::Removed {
+                        account_id: account.id.clone(),
+                    },
+                );
+            }
+        }
+        Ok((removed, stored.active))
+    }
+
+    /// Audit entries of `account_id`, oldest first; empty unless the store
+    /// was built `with_audit`.
+    pub async fn account_history(
+        &self,
+        app_handle: &AppHandle,
+        account_id: &str,
+    ) -> Result<Vec<AuditEntry>> {
+        Ok(self
+            .read_raw(app_handle)
+            .await?
+            .history
+            .into_iter()
+            .filter(|entry| entry.account_id == account_id)
+            .collect())
+    }
+
+    fn audit_upsert(
+        &self,
+        stored: &mut StoredAccounts,
+        previous: Option<&AccountRecord>,
+        account: &AccountRecord,
+    ) {
+        if let Some(max_entries) = self.audit_max_entries
+            && let Some(entry) = AuditEntry::upserted(previous, account, now_millis())
+        {
+            stored.record_audit(entry, max_entries);
+        }
+    }
+
+    pub async fn active_account_id(&self, app_handle: &AppHandle) -> Result<Option<String>> {
+        Ok(self.read_raw(app_handle).await?.active)
+    }
+
+    pub async fn active_account(&self, app_handle: &AppHandle) -> Result<Option<AccountRecord>> {
+        let stored = self.read(app_handle).await?;
+        let Some(active) = stored.active else {
+            return Ok(None);
+        };
+        Ok(stored
+            .accounts
+            .into_iter()
+            .find(|account| account.id == active))
+    }
+
+    /// Makes `account_id` the active account and the most recently used one.
+    /// Returns `None` without changing anything when it is not stored.
+    pub async fn set_active_account(
+        &self,
+        app_handle: &AppHandle,
+        account_id: &str,
+    ) -> Result<Option<AccountRecord>> {
+        let _guard = self.lock_for_write().await?;
+        let mut stored = self.read(app_handle).await?;
+        let Some(account) = stored
+            .accounts
+            .iter()
+            .find(|account| account.id == account_id)
+            .cloned()
+        else {
+            return Ok(None);
+        };
+        stored.activate(account_id);
+        self.write(app_handle, &mut stored).await?;
+        Ok(Some(account))
+    }
+
+    /// Merges records pulled from a remote copy: new ids are added and
+    /// changed ones replaced, except ids with an unexpired tombstone and
+    /// sealed records. Local records missing from `remote` are kept.
+    pub async fn reconcile(
+        &self,
+        app_handle: &AppHandle,
+        remote: Vec<AccountRecord>,
+    ) -> Result<ReconcileReport> {
+        let _guard = self.lock().await;
+        let mut stored = self.read(app_handle).await?;
+        let tombstones_before = stored.tombstones.len();
+        if let Some(ttl) = self.tombstone_ttl {
+            prune_tombstones(&mut stored.tombstones, ttl, now_millis());
+        }
+        let before = stored.accounts.clone();
+        let report = merge_remote_accounts(&mut stored, remote, self.tombstone_ttl.is_some());
+        let changed: Vec<(Option<AccountRecord>, AccountRecord)> = stored
+            .accounts
+            .iter()
+            .filter(|account| {
+                report.added.contains(&account.id) || report.updated.contains(&account.id)
+            })
+            .map(|account| {
+                let previous = before.iter().find(|previous| previous.id == account.id);
+                (previous.cloned(), account.clone())
+            })
+            .collect();
+        for (previous, account) in &changed {
+            self.audit_upsert(&mut stored, previous.as_ref(), account);
+        }
+        if !changed.is_empty() || stored.tombstones.len() != tombstones_before {
+            self.write(app_handle, &mut stored).await?;
+        }
+        for (previous, account) in changed {
+            emit_account_event(
+                self.key(),
+                AccountChange::upserted(previous.as_ref(), account),
+            );
+        }
+        Ok(report)
+    }
+
+    /// Unexpired tombstones; empty when tombstone tracking is disabled.
+    pub async fn list_tombstones(&self, app_handle: &AppHandle) -> Result<Vec<Tombstone>> {
+        let Some(ttl) = self.tombstone_ttl else {
+            return Ok(Vec::new());
+        };
+        let mut tombstones = self.read_raw(app_handle).await?.tombstones;
+        prune_tombstones(&mut tombstones, ttl, now_millis());
+        Ok(tombstones)
+    }
+
+    /// Forgets every stored tombstone and returns how many there were.
+    pub async fn clear_tombstones(&self, app_handle: &AppHandle) -> Result<usize> {
+        let _guard = self.lock().await;
+        let mut stored = self.read(app_handle).await?;
+        let cleared = std::mem::take(&mut stored.tombstones).len();
+        if cleared > 0 {
+            self.write(app_handle, &mut stored).await?;
+        }
+        Ok(cleared)
+    }
+
+    /// Dry run of `remove_accounts_where`: returns the records it would remove
+    /// without touching storage.
+    pub async fn preview_remove_where(
+        &self,
+        app_handle: &AppHandle,
+        predicate: impl Fn(&AccountRecord) -> bool,
+    ) -> Result<Vec<AccountRecord>> {
+        Ok(self
+            .list_accounts(app_handle)
+            .await?
+            .into_iter()
+            .filter(|account| predicate(account))
+            .collect())
+    }
+
+    /// Atomically adds `delta` to the integer counter stored at `extra[key]`
+    /// and returns the new value. A missing key, or a value that is not an
+    /// integer (strings, floats, objects...), counts as 0 and is overwritten.
+    /// Fails with `AccountError::NotFound` when `account_id` is not stored.
+    pub async fn increment_extra_counter(
+        &self,
+        app_handle: &AppHandle,
+        account_id: &str,
+        key: &str,
+        delta: i64,
+    ) -> Result<i64> {
+        let _guard = self.lock_for_write().await?;
+        let mut stored = self.read(app_handle).await?;
+        let (previous, value) = stored.increment_counter(account_id, key, delta)?;
+        let account = stored
+            .find(account_id)
+            .cloned()
+            .expect("the counter was just incremented");
+        self.audit_upsert(&mut stored, Some(&previous), &account);
+        self.write(app_handle, &mut stored).await?;
+        emit_account_event(
+            self.key(),
+            AccountChange::upserted(Some(&previous), account),
+        );
+        Ok(value)
+    }
+
+    /// Login bookkeeping in one write: sets `extra["last_used"]` and
+    /// `extra["updated_at"]` to now (unix ms), increments
+    /// `extra["login_count"]`, and returns the updated record. The account
+    /// also becomes the most recently used one, which the
+    /// `ActiveFallback::MostRecentlyUsed` policy falls back to, but the
+    /// active account is left as is. Returns `None` when `account_id` is not
+    /// stored.
+    pub async fn record_login(
+        &self,
+        app_handle: &AppHandle,
+        account_id: &str,
+    ) -> Result<Option<AccountRecord>> {
+        let _guard = self.lock_for_write().await?;
+        let mut stored = self.read(app_handle).await?;
+        let Some(previous) = stored.record_login(account_id, now_millis())? else {
+            return Ok(None);
+        };
+        let account = stored
+            .find(account_id)
+            .cloned()
+            .expect("the login was just recorded");
+        self.audit_upsert(&mut stored, Some(&previous), &account);
+        self.write(app_handle, &mut stored).await?;
+        emit_account_event(
+            self.key(),
+            AccountChange::upserted(Some(&previous), account.clone()),
+        );
+        Ok(Some(account))
+    }
+
+    /// Marks the record read-only. Returns `None` when `account_id` is not
+    /// stored.
+    pub async fn seal_account(
+        &self,
+        app_handle: &AppHandle,
+        account_id: &str,
+    ) -> Result<Option<AccountRecord>> {
+        self.set_sealed(app_handle, account_id, true).await
+    }
+
+    pub async fn unseal_account(
+        &self,
+        app_handle: &AppHandle,
+        account_id: &str,
+    ) -> Result<Option<AccountRecord>> {
+        self.set_sealed(app_handle, account_id, false).await
+    }
+
+    async fn set_sealed(
+        &self,
+        app_handle: &AppHandle,
+        account_id: &str,
+        sealed: bool,
+    ) -> Result<Option<AccountRecord>> {
+        let _guard = self.lock_for_write().await?;
+        let mut stored = self.read(app_handle).await?;
+        let Some(account) = stored
+            .accounts
+            .iter_mut()
+            .find(|account| account.id == account_id)
+        else {
+            return Ok(None);
+        };
+        if account.sealed == sealed {
+            return Ok(Some(account.clone()));
+        }
+        let previous = account.clone();
+        account.sealed = sealed;
+        let account = account.clone();
+        self.audit_upsert(&mut stored, Some(&previous), &account);
+        self.write(app_handle, &mut stored).await?;
+        emit_account_event(
+            self.key(),
+            AccountChange::upserted(Some(&previous), account.clone()),
+        );
+        Ok(Some(account))
+    }
+
+    /// Yields the record every time `account_id` is written to this store and
+    /// completes once it is removed or the store is cleared. A lagging watcher
+    /// may have missed the removal, so it re-reads the record: it yields the
+    /// current one, or completes when the record is gone. A failed re-read is
+    /// logged and the watch goes on with the next update.
+    pub fn watch_account(
+        &self,
+        app_handle: &AppHandle,
+        account_id: impl Into<String>,
+    ) -> impl Stream<Item = AccountRecord> + Send + 'static {
+        let store = self.clone();
+        let app_handle = app_handle.clone();
+        let account_id = account_id.into();
+        watch_account_events(
+            subscribe_account_events(),
+            self.key.clone(),
+            account_id.clone(),
+            move || {
+                let store = store.clone();
+                let app_handle = app_handle.clone();
+                let account_id = account_id.clone();
+                async move { store.get_account(&app_handle, &account_id).await }.boxed()
+            },
+        )
+    }
+}
+
+/// Event loop of `AccountStore::watch_account`; `refetch` re-reads the record
+/// after `receiver` lagged.
+fn watch_account_events<F>(
+    receiver: broadcast::Receiver<AccountEvent>,
+    store: String,
+    account_id: String,
+    refetch: F,
+) -> impl Stream<Item = AccountRecord> + Send + 'static
+where
+    F: Fn() -> BoxFuture<'static, Result<Option<AccountRecord>>> + Send + 'static,
+{
+    stream::unfold((receiver, refetch), move |(mut receiver, refetch)| {
+        let store = store.clone();
+        let account_id = account_id.clone();
+        async move {
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(missed)) => match refetch().await {
+                        Ok(Some(account)) => return Some((account, (receiver, refetch))),
+                        Ok(None) => return None,
+                        Err(err) => {
+                            log::warn!(
+                                "[AccountStore] {store} watcher of {account_id} lagged by {missed} events: {err:#}"
+                            );
+                            continue;
+                        }
+                    },
+                    Err(RecvError::Closed) => return None,
+                };
+                if event.store != store {
+                    continue;
+                }
+                match event.change {
+                    AccountChange::Upserted { account, .. } if account.id == account_id => {
+                        return Some((account, (receiver, refetch)));
+                    }
+                    AccountChange::Removed {
+                        account_id: removed,
+                    } if removed == account_id => return None,
+                    AccountChange::Cleared => return None,
+                    _ => {}
+                }
+            }
+        }
+    })
+}
+
+/// Compatibility view over `AccountStore` that treats the first stored record
+/// as "the" account, matching the API from before multi-account storage.
+#[derive(Debug, Clone)]
+pub struct SingleAccountStore {
+    store: AccountStore,
+}
+
+impl SingleAccountStore {
+    pub fn store(&self) -> &AccountStore {
+        &self.store
+    }
+
+    pub async fn load(&self, app_handle: &AppHandle) -> Result<Option<AccountRecord>> {
+        Ok(self
+            .store
+            .list_accounts(app_handle)
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Stores `account` as "the" account: the record with the same id is
+    /// replaced, or `account` is added, and it becomes the first record so
+    /// `load` returns it. Other records are left untouched, and a sealed
+    /// record is refused with `AccountError::RecordSealed` as in
+    /// `AccountStore::upsert_account`.
+    pub async fn save(&self, app_handle: &AppHandle, account: &AccountRecord) -> Result<()> {
+        let _guard = self.store.lock_for_write().await?;
+        let mut stored = self.store.read(app_handle).await?;
+        let previous = stored.save_primary(account.clone())?;
+        self.store
+            .audit_upsert(&mut stored, previous.as_ref(), account);
+        self.store.write(app_handle, &mut stored).await?;
+        emit_account_event(
+            self.store.key(),
+            AccountChange::upserted(previous.as_ref(), account.clone()),
+        );
+        Ok(())
+    }
+
+    pub async fn clear(&self, app_handle: &AppHandle) -> Result<()> {
+        self.store.clear(app_handle).await
+    }
+}
+
+/// Removes every `account_provider_*` store whose provider is not in
+/// `known_providers` (matched by normalized name, as `AccountStore::new` does)
+/// and returns the removed keys. Stores created with a custom key or
+/// `for_user` are never touched.
+pub async fn prune_orphaned_stores(
+    app_handle: &AppHandle,
+    known_providers: &[&str],
+) -> Result<Vec<String>> {
+    prune_orphaned_stores_in(app_handle, Arc::new(FrontendStorage), known_providers).await
+}
+
+/// `prune_orphaned_stores` for stores kept in `backend`.
+pub async fn prune_orphaned_stores_in(
+    app_handle: &AppHandle,
+    backend: Arc<dyn StorageBackend>,
+    known_providers: &[&str],
+) -> Result<Vec<String>> {
+    let keys = backend.keys(app_handle, ACCOUNT_STORE_KEY_PREFIX).await?;
+    let orphaned = orphaned_store_keys(keys, known_providers);
+    for key in &orphaned {
+        AccountStore::with_key(key.as_str())
+            .with_backend(Arc::clone(&backend))
+            .clear(app_handle)
+            .await?;
+        log::info!("pruned orphaned account store {key}");
+    }
+    Ok(orphaned)
+}
+
+/// Startup check of every account store, global and per-user, keyed by
+/// store key. Apps should refuse to proceed (or offer to migrate) when any
+/// store is `TooNew`, i.e. the app was downgraded, instead of writing over
+/// data it does not understand. A store that cannot be read is reported as
+/// `Unreadable` and the others are still checked; only failing to list the
+/// store keys fails the whole check.
+pub async fn check_storage_compatibility(
+    app_handle: &AppHandle,
+) -> Result<BTreeMap<String, CompatibilityStatus>> {
+    check_storage_compatibility_in(app_handle, Arc::new(FrontendStorage)).await
+}
+
+/// `check_storage_compatibility` for stores kept in `backend`.
+pub async fn check_storage_compatibility_in(
+    app_handle: &AppHandle,
+    backend: Arc<dyn StorageBackend>,
+) -> Result<BTreeMap<String, CompatibilityStatus>> {
+    let mut keys = backend.keys(app_handle, ACCOUNT_STORE_KEY_PREFIX).await?;
+    keys.extend(
+        backend
+            .keys(app_handle, USER_STORE_KEY_PREFIX)
+            .await?
+            .into_iter()
+            .filter(|key| key.contains(ACCOUNT_STORE_KEY_PREFIX)),
+    );
+    let mut statuses = BTreeMap::new();
+    for key in keys {
+        let check = AccountStore::with_key(key.as_str())
+            .with_backend(Arc::clone(&backend))
+            .compatibility(app_handle)
+            .await;
+        let status = CompatibilityStatus::from_check(check);
+        if let CompatibilityStatus::Unreadable { error } = &status {
+            log::warn!("[AccountStore] {key} is unreadable: {error}");
+        }
+        statuses.insert(key, status);
+    }
+    Ok(statuses)
+}
+
+fn orphaned_store_keys(keys: Vec<String>, known_providers: &[&str]) -> Vec<String> {
+    let known: Vec<String> = known_providers
+        .iter()
+        .map(|provider| AccountStore::new(provider).key().to_string())
+        .collect();
+    keys.into_iter()
+        .filter(|key| key.starts_with(ACCOUNT_STORE_KEY_PREFIX) && !known.contains(key))
+        .collect()
+}
+
+fn write_records<W: std::io::Write>(
+    writer: &mut W,
+    accounts: impl IntoIterator<Item = Result<AccountRecord>>,
+    format: ExportFormat,
+) -> Result<usize> {
+    let mut written = 0;
+    if format == ExportFormat::JsonArray {
+        writer.write_all(b"[")?;
+    }
+    for account in accounts {
+        let account = account?;
+        if format == ExportFormat::JsonArray && written > 0 {
+            writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut *writer, &account)?;
+        if format == ExportFormat::Ndjson {
+            writer.write_all(b"\n")?;
+        }
+        written += 1;
+    }
+    if format == ExportFormat::JsonArray {
+        writer.write_all(b"]")?;
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+fn plan_import(mut stored: StoredAccounts, accounts: &[AccountRecord]) -> ImportPlan {
+    let mut plan = ImportPlan::default();
+    for account in accounts {
+        plan.secret_count += account.secret_count();
+        match stored.apply_upsert(account.clone()) {
+            Ok(None) => plan.added.push(account.id.clone()),
+            Ok(Some(previous)) if previous.semantically_eq(account) => {
+                plan.unchanged.push(account.id.clone())
+            }
+            Ok(Some(_)) => plan.updated.push(account.id.clone()),
+            Err(err) => plan.rejected.push((account.id.clone(), err.to_string())),
+        }
+    }
+    plan.contains_secrets = plan.secret_count > 0;
+    plan
+}
+
+fn touch_login(account: &mut AccountRecord, now: u64) {
+    let count = account
+        .extra_value(LOGIN_COUNT_KEY)
+        .and_then(Value::as_i64)
+        .unwrap_or(0)
+        .saturating_add(1);
+    account.set_extra_value(LOGIN_COUNT_KEY, json!(count));
+    account.set_extra_value(LAST_USED_KEY, json!(now));
+    account.set_extra_value(UPDATED_AT_KEY, json!(now));
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn prune_tombstones(tombstones: &mut Vec<Tombstone>, ttl: Duration, now: u64) {
+    let ttl = ttl.as_millis() as u64;
+    tombstones.retain(|tombstone| now.saturating_sub(tombstone.deleted_at) < ttl);
+}
+
+fn record_tombstones<'a>(
+    tombstones: &mut Vec<Tombstone>,
+    account_ids: impl IntoIterator<Item = &'a str>,
+    now: u64,
+) {
+    for account_id in account_ids {
+        tombstones.retain(|tombstone| tombstone.account_id != account_id);
+        tombstones.push(Tombstone {
+            account_id: account_id.to_string(),
+            deleted_at: now,
+        });
+    }
+}
+
+/// Applies `remote` onto `stored`; expired tombstones must already be pruned.
+fn merge_remote_accounts(
+    stored: &mut StoredAccounts,
+    remote: Vec<AccountRecord>,
+    respect_tombstones: bool,
+) -> ReconcileReport {
+    let mut report = ReconcileReport::default();
+    for account in remote {
+        if account.id.trim().is_empty() {
+            continue;
+        }
+        if respect_tombstones
+            && stored
+                .tombstones
+                .iter()
+                .any(|tombstone| tombstone.account_id == account.id)
+        {
+            report.skipped_tombstoned.push(account.id);
+            continue;
+        }
+        match stored
+            .accounts
+            .iter_mut()
+            .find(|existing| existing.id == account.id)
+        {
+            Some(existing) if existing.semantically_eq(&account) => {}
+            Some(existing) if ensure_unsealed_or_unsealing(existing, &account).is_err() => {
+                report.skipped_sealed.push(account.id);
+            }
+            Some(existing) => {
+                report.updated.push(account.id.clone());
+                *existing = account;
+            }
+            None => {
+                report.added.push(account.id.clone());
+                stored.accounts.push(account);
+            }
+        }
+    }
+    report
+}
+
+/// Drops records without an id and collapses duplicate ids; returns
+/// `(invalid_dropped, duplicates_dropped)`.
+fn canonicalize_accounts(accounts: &mut Vec<AccountRecord>) -> (usize, usize) {
+    let total = accounts.len();
+    accounts.retain(|account| !account.id.trim().is_empty());
+    let invalid_dropped = total - accounts.len();
+
+    let mut canonical: Vec<AccountRecord> = Vec::with_capacity(accounts.len());
+    for account in accounts.drain(..) {
+        match canonical
+            .iter_mut()
+            .find(|existing| existing.id == account.id)
+        {
+            Some(existing) => *existing = account,
+            None => canonical.push(account),
+        }
+    }
+    let duplicates_dropped = total - invalid_dropped - canonical.len();
+    *accounts = canonical;
+    (invalid_dropped, duplicates_dropped)
+}
+
+/// Every secret of the record: the primary token and all purpose tokens.
+fn account_secrets_mut(account: &mut AccountRecord) -> impl Iterator<Item = &mut String> {
+    account
+        .token
+        .iter_mut()
+        .chain(account.tokens.values_mut().map(|entry| &mut entry.value))
+}
+
+fn encrypt_account_secrets(cipher: &dyn TokenCipher, account: &mut AccountRecord) -> Result<()> {
+    for secret in account_secrets_mut(account) {
+        if !cipher.is_encrypted(secret) {
+            *secret = cipher.encrypt(secret)?;
+        }
+    }
+    Ok(())
+}
+
+fn decrypt_account_secrets(cipher: &dyn TokenCipher, account: &mut AccountRecord) -> Result<()> {
+    for secret in account_secrets_mut(account) {
+        if cipher.is_encrypted(secret) {
+            *secret = cipher.decrypt(secret)?;
+        }
+    }
+    Ok(())
+}
+
+fn plaintext_secret_count(cipher: &dyn TokenCipher, account: &AccountRecord) -> usize {
+    account
+        .token
+        .iter()
+        .chain(account.tokens.values().map(|entry| &entry.value))
+        .filter(|secret| !cipher.is_encrypted(secret))
+        .count()
+}
+
+fn ensure_unsealed_or_unsealing(
+    existing: &AccountRecord,
+    incoming: &AccountRecord,
+) -> std::result::Result<(), AccountError> {
+    if !existing.sealed || existing == incoming {
+        return Ok(());
+    }
+    let unsealed = AccountRecord {
+        sealed: false,
+        ..existing.clone()
+    };
+    if *incoming == unsealed {
+        Ok(())
+    } else {
+        Err(AccountError::RecordSealed {
+            account_id: existing.id.clone(),
+        })
+    }
+}
+
+fn normalize_key(input: &str) -> String {
+    input
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() {
+                ch.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TokenEntry;
+    use futures_util::{FutureExt, StreamExt};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn stored_accounts_accept_legacy_array_and_envelope_forms() {
+        let record = json!({ "id": "1", "name": "Alice" });
+
+        let legacy = from_stored_value(record.clone()).unwrap();
+        let array = from_stored_value(json!([record.clone()])).unwrap();
+        let envelope = from_stored_value(json!({ "version": 1, "accounts": [record] })).unwrap();
+
+        for stored in [&legacy, &array, &envelope] {
+            assert_eq!(stored.accounts(), [AccountRecord::new("1", "Alice")]);
+        }
+        assert_eq!(legacy.layout(), StoreLayout::LegacySingle);
+        assert_eq!(array.layout(), StoreLayout::Array);
+        assert_eq!(envelope.layout(), StoreLayout::Envelope);
+        assert_eq!(envelope.version(), ACCOUNT_STORE_SCHEMA_VERSION);
+        assert_eq!(
+            from_stored_value(Value::Null).unwrap().layout(),
+            StoreLayout::Empty
+        );
+        assert!(from_stored_value(json!("garbage")).is_err());
+    }
+
+    #[test]
+    fn canonicalize_keeps_last_duplicate_at_first_position() {
+        let mut accounts = vec![
+            AccountRecord::new("1", "Old Alice"),
+            AccountRecord::new(" ", "No id"),
+            AccountRecord::new("2", "Bob"),
+            AccountRecord::new("1", "Alice"),
+        ];
+
+        assert_eq!(canonicalize_accounts(&mut accounts), (1, 1));
+        assert_eq!(
+            accounts,
+            vec![
+                AccountRecord::new("1", "Alice"),
+                AccountRecord::new("2", "Bob")
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_policy_decides_whether_an_empty_store_keeps_its_key() {
+        let mut stored = StoredAccounts::default();
+        let store = AccountStore::new("xiaomi");
+        assert!(!store.writes_as_removal(&stored));
+        let store = store.with_empty_policy(EmptyPolicy::RemoveKey);
+        assert!(store.writes_as_removal(&stored));
+
+        record_tombstones(&mut stored.tombstones, ["1"], 0);
+        assert!(!store.writes_as_removal(&stored));
+        stored.tombstones.clear();
+        stored.accounts.push(AccountRecord::new("1", "Alice"));
+        assert!(!store.writes_as_removal(&stored));
+    }
+
+    #[test]
+    fn exports_write_json_arrays_and_ndjson() {
+        let accounts = || {
+            ["1", "2"].map(|id| {
+                Ok(AccountRecord::new(id, id)
+                    .with_token("secret".to_string())
+                    .redacted())
+            })
+        };
+        let mut array = Vec::new();
+        assert_eq!(
+            write_records(&mut array, accounts(), ExportFormat::JsonArray).unwrap(),
+            2
+        );
+        let parsed: Vec<AccountRecord> = serde_json::from_slice(&array).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert!(!String::from_utf8(array).unwrap().contains("secret"));
+
+        let mut lines = Vec::new();
+        write_records(&mut lines, accounts(), ExportFormat::Ndjson).unwrap();
+        let lines = String::from_utf8(lines).unwrap();
+        assert_eq!(lines.lines().count(), 2);
+        assert!(lines.ends_with('\n'));
+
+        let mut empty = Vec::new();
+        write_records(&mut empty, std::iter::empty(), ExportFormat::JsonArray).unwrap();
+        assert_eq!(empty, b"[]");
+    }
+
+    #[test]
+    fn exported_records_are_converted_one_value_at_a_time() {
+        let store = AccountStore::with_key("test_export").with_token_cipher(Arc::new(PrefixCipher));
+        let values = || {
+            vec![
+                json!({ "id": "1", "name": "Alice", "token": "enc:secret" }),
+                json!({ "id": "2", "name": "Bob", "token": "plain" }),
+                json!({ "id": 3 }),
+            ]
+        };
+        let tokens = |secrets| {
+            store
+                .exported_records(values(), secrets)
+                .unwrap()
+                .map(|account| account.map(|account| account.token.unwrap()))
+                .take(2)
+                .collect::<Result<Vec<_>>>()
+                .unwrap()
+        };
+        assert_eq!(tokens(ExportSecrets::Plaintext), vec!["secret", "plain"]);
+        assert_eq!(
+            tokens(ExportSecrets::Encrypted),
+            vec!["enc:secret", "enc:plain"]
+        );
+        assert_eq!(
+            tokens(ExportSecrets::Redacted),
+            vec![crate::models::REDACTED; 2]
+        );
+
+        let mut exported = store
+            .exported_records(values(), ExportSecrets::Plaintext)
+            .unwrap();
+        assert!(exported.nth(2).unwrap().is_err());
+        assert!(
+            AccountStore::with_key("test_export")
+                .exported_records(values(), ExportSecrets::Encrypted)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn minting_retries_taken_ids_then_gives_up() {
+        let mut stored = StoredAccounts::default();
+        stored
+            .apply_upsert(AccountRecord::new("1", "taken"))
+            .unwrap();
+        stored
+            .apply_upsert(AccountRecord::new("2", "taken"))
+            .unwrap();
+        let next = std::cell::Cell::new(0);
+        let make = || {
+            next.set(next.get() + 1);
+            AccountRecord::new(next.get().to_string(), "new")
+        };
+
+        assert_eq!(
+            stored
+                .mint_unique(make, DEFAULT_ID_COLLISION_TRIES)
+                .unwrap()
+                .id,
+            "3"
+        );
+        next.set(0);
+        assert!(matches!(
+            stored.mint_unique(make, 2),
+            Err(AccountError::IdCollision { account_id, attempts: 2 }) if account_id == "2"
+        ));
+    }
+
+    #[test]
+    fn schema_compatibility_compares_against_the_current_version() {
+        assert_eq!(
+            schema_compatibility(&Value::Null).unwrap(),
+            CompatibilityStatus::Compatible
+        );
+        assert_eq!(
+            schema_compatibility(
+                &json!({ "version": ACCOUNT_STORE_SCHEMA_VERSION, "accounts": [] })
+            )
+            .unwrap(),
+            CompatibilityStatus::Compatible
+        );
+        assert_eq!(
+            schema_compatibility(&json!([{ "id": "1", "name": "a" }])).unwrap(),
+            CompatibilityStatus::NeedsMigration { version: 0 }
+        );
+        let newer = json!({ "version": ACCOUNT_STORE_SCHEMA_VERSION + 1, "accounts": "?" });
+        let status = schema_compatibility(&newer).unwrap();
+        assert_eq!(
+            status,
+            CompatibilityStatus::TooNew {
+                version: ACCOUNT_STORE_SCHEMA_VERSION + 1
+            }
+        );
+        assert!(!status.is_usable());
+        assert!(schema_compatibility(&json!({ "version": "1", "accounts": [] })).is_err());
+
+        let corrupt = CompatibilityStatus::from_check(schema_compatibility(&json!(42)));
+        assert!(matches!(
+            &corrupt,
+            CompatibilityStatus::Unreadable { error } if error.contains("42")
+        ));
+        assert!(!corrupt.is_usable());
+        assert!(CompatibilityStatus::NeedsMigration { version: 0 }.is_usable());
+    }
+
+    #[test]
+    fn seeding_only_fills_an_empty_store() {
+        let mut stored = StoredAccounts::default();
+        assert!(matches!(
+            stored.seed(vec![AccountRecord::new(" ", "bad")]),
+            Err(AccountError::InvalidRecord { .. })
+        ));
+
+        let mut stored = StoredAccounts::default();
+        assert!(
+            stored
+                .seed(vec![AccountRecord::new("svc", "service")])
+                .unwrap()
+        );
+        assert!(
+            !stored
+                .seed(vec![AccountRecord::new("other", "other")])
+                .unwrap()
+        );
+        assert_eq!(stored.accounts.len(), 1);
+        assert_eq!(stored.accounts[0].id, "svc");
+    }
+
+    #[test]
+    fn user_stores_are_keyed_per_user() {
+        assert_eq!(
+            AccountStore::for_user("Alice", "Mi Fitness").key(),
+            "user_alice_account_provider_mi_fitness"
+        );
+        assert_ne!(
+            AccountStore::for_user("bob", "xiaomi").key(),
+            AccountStore::new("xiaomi").key()
+        );
+    }
+
+    #[test]
+    fn orphaned_keys_match_normalized_provider_names() {
+        let keys = vec![
+            "account_provider_xiaomi".to_string(),
+            "account_provider_old_plugin".to_string(),
+            "account_provider_mi_fitness".to_string(),
+            "settings".to_string(),
+            AccountStore::for_user("alice", "old-plugin")
+                .key()
+                .to_string(),
+        ];
+
+        assert_eq!(
+            orphaned_store_keys(keys, &["Xiaomi", "mi-fitness"]),
+            vec!["account_provider_old_plugin".to_string()]
+        );
+    }
+
+    #[test]
+    fn reconcile_skips_tombstoned_ids_until_they_expire() {
+        let ttl = Duration::from_secs(60);
+        let mut stored = StoredAccounts {
+            accounts: vec![AccountRecord::new("1", "Alice")],
+            ..StoredAccounts::default()
+        };
+        record_tombstones(&mut stored.tombstones, ["2"], 1_000);
+        record_tombstones(&mut stored.tombstones, ["2"], 5_000);
+        assert_eq!(stored.tombstones.len(), 1);
+
+        let remote = vec![
+            AccountRecord::new("1", "Alice"),
+            AccountRecord::new("2", "Bob"),
+            AccountRecord::new("3", "Carol"),
+        ];
+        let report = merge_remote_accounts(&mut stored, remote.clone(), true);
+        assert_eq!(report.added, vec!["3".to_string()]);
+        assert_eq!(report.skipped_tombstoned, vec!["2".to_string()]);
+        assert!(report.updated.is_empty());
+
+        prune_tombstones(&mut stored.tombstones, ttl, 5_000 + 60_000);
+        assert!(stored.tombstones.is_empty());
+        let report = merge_remote_accounts(&mut stored, remote, true);
+        assert_eq!(report.added, vec!["2".to_string()]);
+        assert_eq!(stored.accounts.len(), 3);
+    }
+
+    #[test]
+    fn tombstones_survive_the_envelope_round_trip() {
+        let mut stored = StoredAccounts::default();
+        assert!(
+            !serde_json::to_value(&stored)
+                .unwrap()
+                .as_object()
+                .unwrap()
+                .contains_key("tombstones")
+        );
+        record_tombstones(&mut stored.tombstones, ["1"], 42);
+        let parsed = from_stored_value(serde_json::to_value(&stored).unwrap()).unwrap();
+        assert_eq!(
+            parsed.tombstones(),
+            vec![Tombstone {
+                account_id: "1".to_string(),
+                deleted_at: 42
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn maintenance_mode_refuses_write_locks_only() {
+        static MAINTENANCE: AtomicBool = AtomicBool::new(false);
+        let store =
+            AccountStore::with_key("test_maintenance_mode").with_maintenance_flag(&MAINTENANCE);
+        MAINTENANCE.store(true, Ordering::SeqCst);
+        let refused = store.lock_for_write().await.unwrap_err();
+        drop(store.lock().await);
+        MAINTENANCE.store(false, Ordering::SeqCst);
+
+        assert!(matches!(
+            refused.downcast_ref::<AccountError>(),
+            Some(AccountError::MaintenanceMode { store }) if store == "test_maintenance_mode"
+        ));
+        assert!(store.lock_for_write().await.is_ok());
+    }
+
+    #[test]
+    fn load_flights_are_shared_until_a_write_invalidates_them() {
+        let ready = |value: Value| async move { Ok(Some(value)) }.boxed().shared();
+        let mut flight = LoadFlight::default();
+        let first = flight.join_or_start(|| ready(json!(1)));
+        let joined = flight.join_or_start(|| unreachable!("an in-flight load exists"));
+        assert!(first.ptr_eq(&joined));
+
+        flight.invalidate();
+        let fresh = flight.join_or_start(|| ready(json!(2)));
+        assert!(!fresh.ptr_eq(&first));
+        flight.settle(&first);
+        assert!(flight.in_flight.is_some());
+        flight.settle(&fresh);
+        assert!(flight.in_flight.is_none());
+    }
+
+    #[test]
+    fn failed_acks_map_codes_to_account_errors() {
+        let ack = |value: Value| serde_json::from_value::<LocalStorageAcknowledge>(value).unwrap();
+        assert!(
+            ack(json!({ "success": true }))
+                .into_result("set_json", "k")
+                .is_ok()
+        );
+
+        let err = ack(json!({ "success": false, "code": "quota_exceeded" }))
+            .into_result("set_json", "k")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AccountError>(),
+            Some(AccountError::StorageQuotaExceeded { key }) if key == "k"
+        ));
+        let err = ack(json!({ "success": false, "code": "disk_on_fire", "message": "hot" }))
+            .into_result("remove", "k")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AccountError>(),
+            Some(AccountError::Frontend { code, message }) if code == "disk_on_fire" && message == "hot"
+        ));
+        let err = ack(json!({ "success": false }))
+            .into_result("remove", "k")
+            .unwrap_err();
+        assert!(err.downcast_ref::<AccountError>().is_none());
+    }
+
+    #[test]
+    fn bridge_breaker_opens_then_probes_once() {
+        let mut breaker = BridgeBreaker::new();
+        let start = Instant::now();
+        for _ in 0..BRIDGE_FAILURE_THRESHOLD {
+            assert!(breaker.admit(start).is_ok());
+            breaker.record(false, start);
+        }
+        assert_eq!(breaker.state(start), BridgeBreakerState::Open);
+        assert_eq!(breaker.admit(start), Err(BRIDGE_COOLDOWN));
+
+        let later = start + BRIDGE_COOLDOWN;
+        assert_eq!(breaker.state(later), BridgeBreakerState::HalfOpen);
+        assert!(breaker.admit(later).is_ok());
+        assert!(breaker.admit(later).is_err());
+        breaker.record(false, later);
+        assert_eq!(breaker.state(later), BridgeBreakerState::Open);
+
+        let recovered = later + BRIDGE_COOLDOWN;
+        assert!(breaker.admit(recovered).is_ok());
+        breaker.record(true, recovered);
+        assert_eq!(breaker.state(recovered), BridgeBreakerState::Closed);
+    }
+
+    #[test]
+    fn import_plan_classifies_records_and_counts_secrets() {
+        let mut stored = StoredAccounts::default();
+        stored
+            .apply_upsert(AccountRecord::new("1", "Alice"))
+            .unwrap();
+        let mut sealed = AccountRecord::new("2", "Bob");
+        sealed.sealed = true;
+        stored.apply_upsert(sealed).unwrap();
+
+        let mut with_tokens = AccountRecord::new("3", "Carol");
+        with_tokens.set_token_for("access", TokenEntry::new("abc"));
+        with_tokens.set_token_for("refresh", TokenEntry::new("def"));
+        let redacted = AccountRecord::new("4", "Dan")
+            .with_token("xyz".to_string())
+            .redacted();
+        let plan = plan_import(
+            stored,
+            &[
+                AccountRecord::new("1", "Alice"),
+                AccountRecord::new("2", "Robert"),
+                with_tokens,
+                redacted,
+            ],
+        );
+        assert_eq!(plan.added, vec!["3", "4"]);
+        assert_eq!(plan.unchanged, vec!["1"]);
+        assert!(plan.updated.is_empty());
+        assert_eq!(plan.rejected.len(), 1);
+        assert_eq!(plan.rejected[0].0, "2");
+        assert_eq!(plan.secret_count, 2);
+        assert!(plan.contains_secrets);
+    }
+
+    #[test]
+    fn touch_login_counts_logins_and_stamps_times() {
+        let mut account = AccountRecord::new("1", "Alice");
+        account.set_extra_value(LOGIN_COUNT_KEY, json!("garbage"));
+        touch_login(&mut account, 1_000);
+        touch_login(&mut account, 2_000);
+        assert_eq!(account.extra_value(LOGIN_COUNT_KEY), Some(&json!(2)));
+        assert_eq!(account.extra_value(LAST_USED_KEY), Some(&json!(2_000)));
+        assert_eq!(account.extra_value(UPDATED_AT_KEY), Some(&json!(2_000)));
+    }
+
+    #[test]
+    fn removing_the_active_account_falls_back_to_the_last_login() {
+        let mut stored = StoredAccounts {
+            accounts: ["1", "2", "3"]
+                .map(|id| AccountRecord::new(id, id))
+                .to_vec(),
+            ..StoredAccounts::default()
+        };
+        stored.activate("2");
+        stored.activate("1");
+        assert!(stored.record_login("3", 1_000).unwrap().is_some());
+        assert_eq!(stored.active.as_deref(), Some("1"));
+        assert_eq!(stored.recently_used, vec!["3", "1", "2"]);
+        assert!(stored.record_login("4", 2_000).unwrap().is_none());
+
+        stored.accounts.retain(|account| account.id != "1");
+        stored.repair_active(ActiveFallback::MostRecentlyUsed);
+        assert_eq!(stored.active.as_deref(), Some("3"));
+        assert_eq!(
+            stored.find("3").unwrap().extra_value(LOGIN_COUNT_KEY),
+            Some(&json!(1))
+        );
+    }
+
+    #[test]
+    fn removing_the_active_account_moves_to_the_most_recently_used() {
+        let mut stored = StoredAccounts {
+            accounts: ["1", "2", "3"]
+                .map(|id| AccountRecord::new(id, id))
+                .to_vec(),
+            ..StoredAccounts::default()
+        };
+        stored.activate("3");
+        stored.activate("2");
+        stored.activate("1");
+
+        stored.accounts.retain(|account| account.id != "1");
+        stored.repair_active(ActiveFallback::MostRecentlyUsed);
+        assert_eq!(stored.active.as_deref(), Some("2"));
+        assert_eq!(stored.recently_used, vec!["2", "3"]);
+
+        stored.accounts.retain(|account| account.id != "3");
+        stored.repair_active(ActiveFallback::MostRecentlyUsed);
+        assert_eq!(stored.active.as_deref(), Some("2"));
+        assert_eq!(stored.recently_used, vec!["2"]);
+
+        stored.accounts.clear();
+        stored.repair_active(ActiveFallback::MostRecentlyUsed);
+        assert_eq!(stored.active, None);
+        assert!(stored.recently_used.is_empty());
+
+        stored.accounts.push(AccountRecord::new("4", "4"));
+        stored.repair_active(ActiveFallback::MostRecentlyUsed);
+        assert_eq!(stored.active, None);
+    }
+
+    #[test]
+    fn audit_trail_is_redacted_and_capped_per_account() {
+        let mut stored = StoredAccounts::default();
+        let created = AccountRecord::new("1", "Alice").with_token("secret".to_string());
+        let entry = AuditEntry::upserted(None, &created, 1).unwrap();
+        assert_eq!(entry.operation, AuditOperation::Created);
+        assert!(!serde_json::to_string(&entry).unwrap().contains("secret"));
+        stored.record_audit(entry, 2);
+        assert!(AuditEntry::upserted(Some(&created), &created, 2).is_none());
+
+        let renamed = AccountRecord::new("1", "Alicia").with_token("secret".to_string());
+        let entry = AuditEntry::upserted(Some(&created), &renamed, 3).unwrap();
+        assert_eq!(entry.changes.len(), 1);
+        assert_eq!(entry.changes[0].field, "name");
+        stored.record_audit(entry, 2);
+        stored.record_audit(AuditEntry::removed("2", 4), 2);
+        stored.record_audit(AuditEntry::removed("1", 5), 2);
+
+        let ops: Vec<_> = stored
+            .history
+            .iter()
+            .map(|entry| (entry.account_id.as_str(), entry.operation))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                ("1", AuditOperation::Updated),
+                ("2", AuditOperation::Removed),
+                ("1", AuditOperation::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn compaction_trims_history_to_the_newest_entries_per_account() {
+        let mut stored = StoredAccounts {
+            history: [("1", 1), ("2", 2), ("1", 3), ("1", 4), ("2", 5)]
+                .map(|(id, at)| AuditEntry::removed(id, at))
+                .to_vec(),
+            ..StoredAccounts::default()
+        };
+        assert_eq!(stored.trim_history(2), 1);
+        let kept: Vec<_> = stored
+            .history
+            .iter()
+            .map(|entry| (entry.account_id.as_str(), entry.at))
+            .collect();
+        assert_eq!(kept, vec![("2", 2), ("1", 3), ("1", 4), ("2", 5)]);
+        assert_eq!(stored.trim_history(2), 0);
+        assert_eq!(stored.trim_history(0), 4);
+    }
+
+    #[test]
+    fn active_fallback_strategies_pick_the_configured_account() {
+        let stored_with_history = || {
+            let mut stored = StoredAccounts {
+                accounts: ["1", "2", "3"]
+                    .map(|id| AccountRecord::new(id, id))
+                    .to_vec(),
+                ..StoredAccounts::default()
+            };
+            stored.activate("3");
+            stored.activate("1");
+            stored.accounts.retain(|account| account.id != "1");
+            stored
+        };
+
+        let mut stored = stored_with_history();
+        stored.repair_active(ActiveFallback::FirstInList);
+        assert_eq!(stored.active.as_deref(), Some("2"));
+
+        let mut stored = stored_with_history();
+        stored.repair_active(ActiveFallback::None);
+        assert_eq!(stored.active, None);
+        assert_eq!(stored.recently_used, vec!["3"]);
+    }
+
+    fn arbitrary_value(rng: &mut impl Rng, depth: u32) -> Value {
+        let leaf_only = depth == 0;
+        match rng.gen_range(0..if leaf_only { 5 } else { 7 }) {
+            0 => Value::Null,
+            1 => json!(rng.r#gen::<bool>()),
+            2 => json!(rng.r#gen::<i64>()),
+            3 => json!(rng.r#gen::<f64>()),
+            4 => {
+                let pool = ["", "1", "id", "accounts", "\u{0}", "é", "{\"", "version"];
+                json!(pool[rng.gen_range(0..pool.len())])
+            }
+            5 => Value::Array(
+                (0..rng.gen_range(0..4))
+                    .map(|_| arbitrary_value(rng, depth - 1))
+                    .collect(),
+            ),
+            _ => {
+                let keys = [
+                    "id",
+                    "name",
+                    "avatar",
+                    "token",
+                    "extra",
+                    "sealed",
+                    "version",
+                    "accounts",
+                    "tombstones",
+                    "active",
+                    "recently_used",
+                    "other",
+                ];
+                Value::Object(
+                    (0..rng.gen_range(0..5))
+                        .map(|_| {
+                            let key = keys[rng.gen_range(0..keys.len())].to_string();
+                            (key, arbitrary_value(rng, depth - 1))
+                        })
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    #[test]
+    fn from_stored_value_never_panics_on_arbitrary_json() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let valid = json!({
+            "version": 1,
+            "accounts": [{ "id": "1", "name": "Alice", "extra": {} }],
+            "active": "1",
+        });
+        for _ in 0..5_000 {
+            let _ = from_stored_value(arbitrary_value(&mut rng, 4));
+
+            let mut mutated = valid.clone();
+            let field = ["version", "accounts", "active", "tombstones"][rng.gen_range(0..4)];
+            mutated[field] = arbitrary_value(&mut rng, 3);
+            let _ = from_stored_value(mutated.clone());
+            mutated["accounts"] = json!([arbitrary_value(&mut rng, 3)]);
+            let _ = from_stored_value(mutated);
+        }
+    }
+
+    /// Reversible stand-in cipher for exercising the secret helpers.
+    struct PrefixCipher;
+
+    impl TokenCipher for PrefixCipher {
+        fn encrypt(&self, plain: &str) -> Result<String> {
+            Ok(format!("enc:{plain}"))
+        }
+
+        fn decrypt(&self, stored: &str) -> Result<String> {
+            Ok(stored.trim_start_matches("enc:").to_string())
+        }
+
+        fn is_encrypted(&self, stored: &str) -> bool {
+            stored.starts_with("enc:")
+        }
+    }
+
+    #[test]
+    fn secret_encryption_is_idempotent() {
+        let mut account = AccountRecord::new("1", "Alice").with_token(Some("secret".to_string()));
+        assert_eq!(plaintext_secret_count(&PrefixCipher, &account), 1);
+
+        encrypt_account_secrets(&PrefixCipher, &mut account).unwrap();
+        encrypt_account_secrets(&PrefixCipher, &mut account).unwrap();
+        assert_eq!(account.token.as_deref(), Some("enc:secret"));
+        assert_eq!(plaintext_secret_count(&PrefixCipher, &account), 0);
+
+        decrypt_account_secrets(&PrefixCipher, &mut account).unwrap();
+        decrypt_account_secrets(&PrefixCipher, &mut account).unwrap();
+        assert_eq!(account.token.as_deref(), Some("secret"));
+    }
+
+    #[test]
+    fn secret_encryption_covers_purpose_tokens() {
+        let mut account = AccountRecord::new("1", "Alice");
+        account.set_token_for("refresh", TokenEntry::new("r-1"));
+        account.set_token_for("access", TokenEntry::new("a-1"));
+        assert_eq!(plaintext_secret_count(&PrefixCipher, &account), 3);
+
+        encrypt_account_secrets(&PrefixCipher, &mut account).unwrap();
+        assert_eq!(plaintext_secret_count(&PrefixCipher, &account), 0);
+        assert_eq!(account.tokens["refresh"].value, "enc:r-1");
+
+        decrypt_account_secrets(&PrefixCipher, &mut account).unwrap();
+        assert_eq!(account.token_for("refresh"), Some("r-1"));
+        assert_eq!(account.token.as_deref(), Some("a-1"));
+    }
+
+    #[test]
+    fn batch_upserts_apply_in_order_and_report_rejections() {
+        let mut stored = StoredAccounts {
+            accounts: vec![AccountRecord {
+                sealed: true,
+                ..AccountRecord::new("sealed", "Locked")
+            }],
+            ..StoredAccounts::default()
+        };
+        assert_eq!(
+            stored
+                .apply_upsert(AccountRecord::new("1", "Alice"))
+                .unwrap(),
+            None
+        );
+        assert!(matches!(
+            stored.apply_upsert(AccountRecord::new(" ", "No id")),
+            Err(AccountError::InvalidRecord { .. })
+        ));
+        assert!(matches!(
+            stored.apply_upsert(AccountRecord::new("sealed", "Changed")),
+            Err(AccountError::RecordSealed { .. })
+        ));
+        assert_eq!(
+            stored
+                .apply_upsert(AccountRecord::new("1", "Alice W"))
+                .unwrap()
+                .map(|previous| previous.name),
+            Some("Alice".to_string())
+        );
+        assert_eq!(stored.accounts.len(), 2);
+    }
+
+    #[test]
+    fn single_account_saves_keep_the_other_records() {
+        let mut stored = StoredAccounts {
+            accounts: ["1", "2", "3"]
+                .map(|id| AccountRecord::new(id, id))
+                .to_vec(),
+            ..StoredAccounts::default()
+        };
+
+        let previous = stored.save_primary(AccountRecord::new("2", "Bob")).unwrap();
+        assert_eq!(previous.map(|account| account.name), Some("2".to_string()));
+        assert_eq!(
+            stored.save_primary(AccountRecord::new("4", "Dan")).unwrap(),
+            None
+        );
+
+        let accounts: Vec<_> = stored
+            .accounts
+            .iter()
+            .map(|account| (account.id.as_str(), account.name.as_str()))
+            .collect();
+        assert_eq!(
+            accounts,
+            [("4", "Dan"), ("2", "Bob"), ("1", "1"), ("3", "3")]
+        );
+    }
+
+    #[test]
+    fn single_account_saves_respect_seals() {
+        let mut stored = StoredAccounts {
+            accounts: vec![AccountRecord {
+                sealed: true,
+                ..AccountRecord::new("1", "Alice")
+            }],
+            ..StoredAccounts::default()
+        };
+        assert!(matches!(
+            stored.save_primary(AccountRecord::new("1", "Mallory")),
+            Err(AccountError::RecordSealed { account_id }) if account_id == "1"
+        ));
+        assert_eq!(stored.accounts[0].name, "Alice");
+    }
+
+    #[test]
+    fn counters_start_at_zero_and_report_missing_accounts() {
+        let mut stored = StoredAccounts {
+            accounts: vec![AccountRecord::new("1", "Alice")],
+            ..StoredAccounts::default()
+        };
+        stored.accounts[0].set_extra_value("logins", json!("many"));
+        let (previous, value) = stored.increment_counter("1", "logins", 2).unwrap();
+        assert_eq!(previous.extra_value("logins"), Some(&json!("many")));
+        assert_eq!(value, 2);
+        assert_eq!(stored.increment_counter("1", "logins", 3).unwrap().1, 5);
+        assert_eq!(stored.accounts[0].extra_value("logins"), Some(&json!(5)));
+        assert!(matches!(
+            stored.increment_counter("2", "logins", 1),
+            Err(AccountError::NotFound { account_id }) if account_id == "2"
+        ));
+    }
+
+    #[test]
+    fn sealed_records_only_accept_unsealing() {
+        let mut sealed = AccountRecord::new("1", "Alice");
+        sealed.sealed = true;
+        let renamed = AccountRecord {
+            name: "Mallory".to_string(),
+            ..sealed.clone()
+        };
+        let unsealed = AccountRecord {
+            sealed: false,
+            ..sealed.clone()
+        };
+
+        assert!(ensure_unsealed_or_unsealing(&sealed, &sealed).is_ok());
+        assert!(ensure_unsealed_or_unsealing(&sealed, &unsealed).is_ok());
+        let err = ensure_unsealed_or_unsealing(&sealed, &renamed).unwrap_err();
+        assert!(matches!(
+            err,
+            AccountError::RecordSealed { account_id } if account_id == "1"
+        ));
+        assert!(ensure_unsealed_or_unsealing(&unsealed, &renamed).is_ok());
+    }
+
+    /// `watch_account_events` on a private channel, so other tests' events
+    /// neither reach it nor make it lag. `refetch` answers `stored`.
+    fn watch_on_private_channel(
+        capacity: usize,
+        stored: Option<AccountRecord>,
+    ) -> (
+        broadcast::Sender<AccountEvent>,
+        impl Stream<Item = AccountRecord>,
+    ) {
+        let (sender, receiver) = broadcast::channel(capacity);
+        let refetch = move || {
+            let stored = stored.clone();
+            async move { Ok(stored) }.boxed()
+        };
+        let updates =
+            watch_account_events(receiver, "watched".to_string(), "1".to_string(), refetch);
+        (sender, updates)
+    }
+
+    fn event(store: &str, change: AccountChange) -> AccountEvent {
+        AccountEvent {
+            seq: 0,
+            store: store.to_string(),
+            change,
+        }
+    }
+
+    #[test]
+    fn watch_account_yields_updates_until_removed() {
+        let (sender, updates) = watch_on_private_channel(8, None);
+        let mut updates = Box::pin(updates);
+
+        for (store, change) in [
+            (
+                "watched",
+                AccountChange::upserted(None, AccountRecord::new("2", "Bob")),
+            ),
+            (
+                "other_store",
+                AccountChange::upserted(None, AccountRecord::new("1", "Elsewhere")),
+            ),
+            (
+                "watched",
+                AccountChange::upserted(None, AccountRecord::new("1", "Alice")),
+            ),
+            (
+                "watched",
+                AccountChange::Removed {
+                    account_id: "1".to_string(),
+                },
+            ),
+        ] {
+            sender.send(event(store, change)).unwrap();
+        }
+
+        assert_eq!(
+            updates
+                .next()
+                .now_or_never()
+                .flatten()
+                .map(|account| account.name),
+            Some("Alice".to_string())
+        );
+        assert_eq!(updates.next().now_or_never(), Some(None));
+    }
+
+    #[test]
+    fn lagging_watchers_re_read_the_record() {
+        let overflow = |sender: &broadcast::Sender<AccountEvent>| {
+            for _ in 0..3 {
+                let added = AccountChange::upserted(None, AccountRecord::new("2", "Bob"));
+                sender.send(event("watched", added)).unwrap();
+            }
+        };
+
+        let (sender, updates) = watch_on_private_channel(2, None);
+        let mut updates = Box::pin(updates);
+        overflow(&sender);
+        assert_eq!(updates.next().now_or_never(), Some(None));
+
+        let (sender, updates) = watch_on_private_channel(2, Some(AccountRecord::new("1", "Alice")));
+        let mut updates = Box::pin(updates);
+        overflow(&sender);
+        assert_eq!(
+            updates
+                .next()
+                .now_or_never()
+                .flatten()
+                .map(|account| account.name),
+            Some("Alice".to_string())
+        );
+    }
 }
@@ -0,0 +1,238 @@
+//! Configurable in-memory `AccountProvider` shared by the unit tests.
+
+use crate::AccountProvider;
+use crate::error::{AccountError, TokenFormatError};
+use crate::models::{AccountRecord, Capability, ProviderMetadata};
+use async_trait::async_trait;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Notify;
+
+/// Keeps its accounts in memory (upserts replace by id) and lets each test
+/// switch on only the behaviour it exercises.
+pub(crate) struct TestProvider {
+    name: String,
+    accounts: Mutex<Vec<AccountRecord>>,
+    display_name: Option<String>,
+    translations: Vec<(&'static str, &'static str)>,
+    api_version: Option<u32>,
+    capabilities: Option<Vec<Capability>>,
+    required_extra: Vec<&'static str>,
+    token_prefix: Option<&'static str>,
+    retryable: Option<fn(&AccountError) -> bool>,
+    refreshable: bool,
+    failing_refresh: Vec<&'static str>,
+    gate: Option<RefreshGate>,
+    pub refreshes: AtomicUsize,
+    pub shutdowns: AtomicUsize,
+}
+
+/// Makes `refresh` wait for `release`, optionally failing the first call.
+pub(crate) struct RefreshGate {
+    pub release: Notify,
+    fail_first: bool,
+}
+
+impl TestProvider {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            accounts: Mutex::new(Vec::new()),
+            display_name: None,
+            translations: Vec::new(),
+            api_version: None,
+            capabilities: None,
+            required_extra: Vec::new(),
+            token_prefix: None,
+            retryable: None,
+            refreshable: false,
+            failing_refresh: Vec::new(),
+            gate: None,
+            refreshes: AtomicUsize::new(0),
+            shutdowns: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_accounts(self, accounts: impl IntoIterator<Item = AccountRecord>) -> Self {
+        *self.accounts.lock().unwrap() = accounts.into_iter().collect();
+        self
+    }
+
+    /// Accounts named after their ids.
+    pub fn with_ids(self, ids: &[&str]) -> Self {
+        self.with_accounts(ids.iter().map(|id| AccountRecord::new(*id, *id)))
+    }
+
+    pub fn display_name(mut self, name: impl Into<String>) -> Self {
+        self.display_name = Some(name.into());
+        self
+    }
+
+    pub fn translated(mut self, locale: &'static str, display_name: &'static str) -> Self {
+        self.translations.push((locale, display_name));
+        self
+    }
+
+    pub fn api_version(mut self, version: u32) -> Self {
+        self.api_version = Some(version);
+        self
+    }
+
+    pub fn capabilities(mut self, capabilities: Vec<Capability>) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    pub fn requiring(mut self, keys: &[&'static str]) -> Self {
+        self.required_extra = keys.to_vec();
+        self
+    }
+
+    /// Rejects tokens without `prefix` in `validate_token_format`.
+    pub fn token_prefix(mut self, prefix: &'static str) -> Self {
+        self.token_prefix = Some(prefix);
+        self
+    }
+
+    pub fn retryable(mut self, classify: fn(&AccountError) -> bool) -> Self {
+        self.retryable = Some(classify);
+        self
+    }
+
+    /// Supports `refresh_token`, which sets the primary token to `"rotated"`
+    /// (with `with_token`, like a careless provider would) except for the
+    /// `failing` ids.
+    pub fn refreshable(mut self, failing: &[&'static str]) -> Self {
+        self.refreshable = true;
+        self.failing_refresh = failing.to_vec();
+        self
+    }
+
+    pub fn gated(mut self, fail_first: bool) -> Self {
+        self.gate = Some(RefreshGate {
+            release: Notify::new(),
+            fail_first,
+        });
+        self
+    }
+
+    pub fn gate(&self) -> &RefreshGate {
+        self.gate.as_ref().expect("provider is not gated")
+    }
+
+    pub fn stored(&self) -> Vec<AccountRecord> {
+        self.accounts.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl AccountProvider for TestProvider {
+    fn provider_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn api_version(&self) -> u32 {
+        self.api_version
+            .unwrap_or(crate::ACCOUNT_PROVIDER_API_VERSION)
+    }
+
+    fn capabilities(&self) -> Vec<Capability> {
+        match &self.capabilities {
+            Some(capabilities) => capabilities.clone(),
+            None if self.refreshable => vec![Capability::TokenRefresh],
+            None => Vec::new(),
+        }
+    }
+
+    fn metadata(&self) -> ProviderMetadata {
+        ProviderMetadata {
+            display_name: self
+                .display_name
+                .clone()
+                .unwrap_or_else(|| self.name.clone()),
+            description: None,
+        }
+    }
+
+    fn metadata_localized(&self, locale: &str) -> ProviderMetadata {
+        match self.translations.iter().find(|(tag, _)| *tag == locale) {
+            Some((_, display_name)) => ProviderMetadata {
+                display_name: display_name.to_string(),
+                description: None,
+            },
+            None => self.metadata(),
+        }
+    }
+
+    async fn refresh(&self) -> anyhow::Result<()> {
+        let call = self.refreshes.fetch_add(1, Ordering::SeqCst);
+        if let Some(gate) = &self.gate {
+            gate.release.notified().await;
+            if gate.fail_first && call == 0 {
+                anyhow::bail!("upstream unavailable");
+            }
+        }
+        Ok(())
+    }
+
+    fn supports_refresh(&self) -> bool {
+        self.refreshable
+    }
+
+    async fn refresh_token(&self, account: &AccountRecord) -> anyhow::Result<AccountRecord> {
+        if !self.refreshable {
+            anyhow::bail!("{} does not support token refresh", self.name);
+        }
+        if self.failing_refresh.contains(&account.id.as_str()) {
+            anyhow::bail!("refresh token revoked");
+        }
+        Ok(account.clone().with_token("rotated".to_string()))
+    }
+
+    async fn shutdown(&self) -> anyhow::Result<()> {
+        self.shutdowns.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn list_accounts(&self) -> anyhow::Result<Vec<AccountRecord>> {
+        Ok(self.stored())
+    }
+
+    fn required_extra_keys(&self) -> &[&str] {
+        &self.required_extra
+    }
+
+    fn validate_token_format(&self, token: &str) -> Result<(), TokenFormatError> {
+        match self.token_prefix {
+            Some(prefix) if !token.starts_with(prefix) => Err(TokenFormatError::new(format!(
+                "expected a `{prefix}` prefixed token"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    async fn store_account(&self, account: AccountRecord) -> anyhow::Result<AccountRecord> {
+        let mut accounts = self.accounts.lock().unwrap();
+        match accounts
+            .iter_mut()
+            .find(|existing| existing.id == account.id)
+        {
+            Some(existing) => *existing = account.clone(),
+            None => accounts.push(account.clone()),
+        }
+        Ok(account)
+    }
+
+    async fn remove_account(&self, account_id: &str) -> anyhow::Result<Option<AccountRecord>> {
+        let mut accounts = self.accounts.lock().unwrap();
+        let index = accounts.iter().position(|account| account.id == account_id);
+        Ok(index.map(|index| accounts.remove(index)))
+    }
+
+    fn is_retryable(&self, err: &AccountError) -> bool {
+        match self.retryable {
+            Some(classify) => classify(err),
+            None => err.is_transient(),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::login::LoginResponseMapper;
 use crate::models::AccountRecord;
 use aes::Aes128;
 use anyhow::{Context, Result, anyhow};
@@ -208,6 +209,15 @@ struct VivoOtaResponse<T> {
     data: Option<T>,
 }
 
+impl LoginResponseMapper for VivoAccountProfile {
+    fn to_account_record(self) -> Result<AccountRecord> {
+        if self.open_id.trim().is_empty() {
+            return Err(anyhow!("vivo login response has no openId"));
+        }
+        Ok(build_account_record(&self))
+    }
+}
+
 pub fn build_account_record(profile: &VivoAccountProfile) -> AccountRecord {
     let profile = profile.clone().normalize();
     let mut record = AccountRecord::new(profile.open_id.clone(), profile.display_name())
@@ -697,6 +707,18 @@ fn normalize_vivo_mac_address(mac_address: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn login_mapper_requires_an_open_id() {
+        let mut profile = VivoAccountProfile::new(" open-1 ");
+        profile.nick_name = Some("Alice".to_string());
+        let record = profile.clone().to_account_record().unwrap();
+        assert_eq!(record.id, "open-1");
+        assert_eq!(record.name, "Alice");
+
+        profile.open_id = "  ".to_string();
+        assert!(profile.to_account_record().is_err());
+    }
+
     #[test]
     fn vivo_base64_decodes_official_plaintext_response() {
         let decoded = vivo_base64_decode("ADJex0_uEejDTNQsE2C96DEIE2mz3K").unwrap();